@@ -0,0 +1,280 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pss, RsaPublicKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha512};
+
+use crate::error::{Error, Result};
+use crate::models::{cask, formula};
+
+pub const FORMULAE_URL: &str = "https://formulae.brew.sh/api/formula.jws.json";
+pub const CASKS_URL: &str = "https://formulae.brew.sh/api/cask.jws.json";
+
+pub const FORMULAE_FILE: &str = "formula.jws.json";
+pub const CASKS_FILE: &str = "cask.jws.json";
+
+/// Key id of the signature made with the Homebrew key
+const KEY_ID: &str = "homebrew-1";
+const KEY_FILE: &str = "homebrew-1.pem";
+
+const ALGORITHM: &str = "PS512";
+
+/// Loader of the formulae.brew.sh JSON API payloads.
+///
+/// Payloads are wrapped in a JWS envelope signed by Homebrew,
+/// the signature is checked only if the public key is given.
+pub struct Api {
+    key: Option<RsaPublicKey>,
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    payload: String,
+    signatures: Vec<Signature>,
+}
+
+#[derive(Deserialize)]
+struct Signature {
+    protected: String,
+    header: SignatureHeader,
+    signature: String,
+}
+
+#[derive(Deserialize)]
+struct SignatureHeader {
+    kid: String,
+}
+
+#[derive(Deserialize)]
+struct ProtectedHeader {
+    alg: String,
+    b64: Option<bool>,
+}
+
+impl Api {
+    pub fn new(key: Option<RsaPublicKey>) -> Api {
+        Api { key }
+    }
+
+    /// Read the Homebrew public key in PEM format
+    pub fn read_key(path: &Path) -> Result<RsaPublicKey> {
+        let pem = fs::read_to_string(path).map_err(Error::io(path))?;
+
+        RsaPublicKey::from_public_key_pem(&pem)
+            .map_err(|e| Error::Signature(format!("invalid public key {}: {e}", path.display())))
+    }
+
+    /// Locations of the public key shipped with the Homebrew installation
+    pub fn key_candidates(prefix: &Path) -> Vec<PathBuf> {
+        let library = Path::new("Library")
+            .join("Homebrew")
+            .join("api")
+            .join(KEY_FILE);
+
+        vec![
+            prefix.join(&library),
+            prefix.join("Homebrew").join(&library),
+        ]
+    }
+
+    /// Directory where Homebrew keeps the downloaded API payloads
+    pub fn homebrew_cache_dir() -> Option<PathBuf> {
+        if let Some(cache) = std::env::var_os("HOMEBREW_CACHE").filter(|c| !c.is_empty()) {
+            return Some(PathBuf::from(cache).join("api"));
+        }
+
+        let home = PathBuf::from(std::env::var_os("HOME")?);
+
+        #[cfg(target_os = "macos")]
+        let cache = home.join("Library").join("Caches");
+
+        #[cfg(not(target_os = "macos"))]
+        let cache = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|c| !c.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or(home.join(".cache"));
+
+        Some(cache.join("Homebrew").join("api"))
+    }
+
+    pub fn formulae(&self, data: &[u8]) -> Result<formula::base::Store> {
+        let payload = self.unwrap(data)?;
+        let formulae: Vec<formula::base::Formula> =
+            serde_json::from_str(&payload).map_err(Error::schema("formulae"))?;
+
+        Ok(formulae.into_iter().map(|f| (f.full_name(), f)).collect())
+    }
+
+    pub fn casks(&self, data: &[u8]) -> Result<cask::base::Store> {
+        let payload = self.unwrap(data)?;
+        let casks: Vec<cask::base::Cask> =
+            serde_json::from_str(&payload).map_err(Error::schema("casks"))?;
+
+        Ok(casks.into_iter().map(|c| (c.full_token(), c)).collect())
+    }
+
+    /// Payload of the JWS envelope, verified if the key is present
+    fn unwrap(&self, data: &[u8]) -> Result<String> {
+        let envelope: Envelope =
+            serde_json::from_slice(data).map_err(Error::schema("JWS envelope"))?;
+
+        if let Some(key) = &self.key {
+            let signature = envelope
+                .signatures
+                .iter()
+                .find(|s| s.header.kid == KEY_ID)
+                .ok_or(Error::Signature(format!("missing {KEY_ID} signature")))?;
+
+            Self::verify(key, &envelope.payload, signature)?;
+        }
+
+        Ok(envelope.payload)
+    }
+
+    fn verify(key: &RsaPublicKey, payload: &str, signature: &Signature) -> Result<()> {
+        let header: ProtectedHeader = URL_SAFE_NO_PAD
+            .decode(signature.protected.trim_end_matches('='))
+            .ok()
+            .and_then(|h| serde_json::from_slice(&h).ok())
+            .ok_or(Error::Signature("malformed signature header".to_string()))?;
+
+        // payload is not base64 encoded, see RFC 7797
+        if header.alg != ALGORITHM || header.b64 != Some(false) {
+            return Err(Error::Signature(format!(
+                "unsupported signature algorithm {}",
+                header.alg
+            )));
+        }
+
+        let signing_input = format!("{}.{payload}", signature.protected);
+        let digest = Sha512::digest(signing_input.as_bytes());
+
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature.signature.trim_end_matches('='))
+            .map_err(|_| Error::Signature("malformed signature".to_string()))?;
+
+        key.verify(
+            Pss::new_with_salt::<Sha512>(Sha512::output_size()),
+            &digest,
+            &signature,
+        )
+        .map_err(|_| Error::Signature("signature mismatch".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::Value;
+
+    use super::Api;
+    use crate::error::Error;
+
+    const FORMULAE: &[u8] = include_bytes!("../tests/fixtures/api/formula.jws.json");
+    const UNSIGNED_CASKS: &[u8] = include_bytes!("../tests/fixtures/api/cask.jws.json");
+
+    fn verifying() -> Api {
+        let key = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/api/key.pem");
+
+        Api::new(Some(Api::read_key(&key).unwrap()))
+    }
+
+    /// Formulae envelope with the given change applied
+    fn tampered(change: impl FnOnce(&mut Value)) -> Vec<u8> {
+        let mut envelope: Value = serde_json::from_slice(FORMULAE).unwrap();
+
+        change(&mut envelope);
+
+        serde_json::to_vec(&envelope).unwrap()
+    }
+
+    fn signature_error(result: crate::Result<impl Sized>) -> String {
+        match result {
+            Err(Error::Signature(message)) => message,
+            Err(e) => panic!("expected a signature error, got {e}"),
+            Ok(_) => panic!("expected a signature error"),
+        }
+    }
+
+    #[test]
+    fn signed_payload_is_verified_and_parsed() {
+        let formulae = verifying().formulae(FORMULAE).unwrap();
+
+        assert_eq!(formulae.len(), 2);
+        assert_eq!(formulae["wget"].pkg_version(), "1.24.5_1");
+        assert_eq!(formulae["acme/tools/hello"].name, "hello");
+    }
+
+    #[test]
+    fn unsigned_payload_is_parsed_without_a_key() {
+        let casks = Api::new(None).casks(UNSIGNED_CASKS).unwrap();
+
+        assert_eq!(casks["firefox"].version, "130.0");
+        assert!(casks["firefox"].names.contains("Mozilla Firefox"));
+    }
+
+    #[test]
+    fn unsigned_payload_is_rejected_with_a_key() {
+        let message = signature_error(verifying().casks(UNSIGNED_CASKS));
+
+        assert_eq!(message, "missing homebrew-1 signature");
+    }
+
+    #[test]
+    fn unknown_key_id_is_rejected() {
+        let data = tampered(|e| e["signatures"][0]["header"]["kid"] = "homebrew-2".into());
+
+        assert_eq!(
+            signature_error(verifying().formulae(&data)),
+            "missing homebrew-1 signature"
+        );
+
+        // the signature is only checked with a key
+        assert_eq!(Api::new(None).formulae(&data).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn modified_payload_is_a_signature_mismatch() {
+        let data = tampered(|e| {
+            let payload = e["payload"].as_str().unwrap().replace("1.24.5", "1.24.6");
+
+            e["payload"] = payload.into();
+        });
+
+        assert_eq!(
+            signature_error(verifying().formulae(&data)),
+            "signature mismatch"
+        );
+    }
+
+    #[test]
+    fn unsupported_algorithm_is_rejected() {
+        // {"alg":"RS256","b64":false}
+        let data = tampered(|e| {
+            e["signatures"][0]["protected"] = "eyJhbGciOiJSUzI1NiIsImI2NCI6ZmFsc2V9".into()
+        });
+
+        assert_eq!(
+            signature_error(verifying().formulae(&data)),
+            "unsupported signature algorithm RS256"
+        );
+    }
+
+    #[test]
+    fn malformed_envelope_is_a_schema_error() {
+        let result = Api::new(None).formulae(br#"{"payload": "[]"}"#);
+
+        assert!(matches!(
+            result,
+            Err(Error::Schema {
+                what: "JWS envelope",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,862 @@
+//! Brewfiles as read and written by `brew bundle`.
+//!
+//! Only `tap`, `brew` and `cask` entries are supported, lines with other entries
+//! such as `mas` or `vscode` are skipped. Every entry sits on its own line, options
+//! of `brew` other than `args` and `link` and every option of `cask` are ignored.
+
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::{Chars, FromStr};
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::models::{cask, formula, keg, State};
+use crate::resolve::{self, Resolver};
+
+#[derive(Clone, Default, Debug)]
+pub struct Brewfile {
+    pub entries: Vec<Entry>,
+
+    /// Line numbers and contents of the unsupported entries
+    pub skipped: Vec<(usize, String)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Entry {
+    Tap(Tap),
+    Brew(Formula),
+    Cask(Cask),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tap {
+    pub name: String,
+
+    /// Remote of the tap, None for the GitHub repository the name points to
+    pub url: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Formula {
+    pub name: String,
+
+    /// Options of `brew install` without the leading dashes, e.g. `HEAD`
+    pub args: Vec<String>,
+
+    /// Whether the formula is linked after the install, None leaves it to Homebrew
+    pub link: Option<bool>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cask {
+    pub name: String,
+}
+
+/// Entry of a Brewfile which is not installed
+#[derive(Serialize, Clone, Debug)]
+pub struct Missing {
+    #[serde(rename = "type")]
+    pub kind: Kind,
+
+    /// Name as given in the Brewfile
+    pub name: String,
+
+    /// Whether the cache knows the tap, formula or cask
+    pub known: bool,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Tap,
+    Formula,
+    Cask,
+}
+
+impl Brewfile {
+    pub fn read(path: &Path) -> Result<Brewfile> {
+        let data = std::fs::read_to_string(path).map_err(Error::io(path))?;
+
+        data.parse()
+    }
+
+    /// Taps, formulae installed on request and casks, ordered as by `brew bundle dump`
+    pub fn dump(formulae: &formula::installed::Store, casks: &cask::installed::Store) -> Brewfile {
+        let formulae: Vec<_> = formulae
+            .values()
+            .filter(|f| f.receipt.installed_on_request)
+            .collect();
+
+        let taps: BTreeSet<&str> = formulae
+            .iter()
+            .map(|f| f.upstream.base.tap.as_str())
+            .chain(casks.values().map(|c| c.upstream.base.tap.as_str()))
+            .filter(|tap| !keg::DEFAULT_TAPS.contains(tap))
+            .collect();
+
+        let mut brews: Vec<_> = formulae
+            .iter()
+            .map(|f| Formula {
+                name: f.upstream.base.full_name(),
+                args: match f.receipt.source.spec {
+                    formula::receipt::Spec::Stable => Vec::new(),
+                    formula::receipt::Spec::Head => vec!["HEAD".to_string()],
+                },
+                link: None,
+            })
+            .collect();
+
+        let mut casks: Vec<_> = casks
+            .values()
+            .map(|c| Cask {
+                name: c.upstream.base.full_token(),
+            })
+            .collect();
+
+        brews.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        casks.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+        let taps = taps.into_iter().map(|name| {
+            Entry::Tap(Tap {
+                name: name.to_string(),
+                url: None,
+            })
+        });
+
+        Brewfile {
+            entries: taps
+                .chain(brews.into_iter().map(Entry::Brew))
+                .chain(casks.into_iter().map(Entry::Cask))
+                .collect(),
+            skipped: Vec::new(),
+        }
+    }
+
+    pub fn taps(&self) -> impl Iterator<Item = &Tap> {
+        self.entries.iter().filter_map(|e| match e {
+            Entry::Tap(tap) => Some(tap),
+            _ => None,
+        })
+    }
+
+    pub fn formulae(&self) -> impl Iterator<Item = &Formula> {
+        self.entries.iter().filter_map(|e| match e {
+            Entry::Brew(formula) => Some(formula),
+            _ => None,
+        })
+    }
+
+    pub fn casks(&self) -> impl Iterator<Item = &Cask> {
+        self.entries.iter().filter_map(|e| match e {
+            Entry::Cask(cask) => Some(cask),
+            _ => None,
+        })
+    }
+
+    /// Entries which are not installed, in the order of the Brewfile.
+    /// Taps are installed if they are tapped, see [crate::taps::tapped], or default ones
+    pub fn missing(
+        &self,
+        state: &State<formula::State, cask::State>,
+        tapped: &BTreeSet<String>,
+    ) -> Vec<Missing> {
+        let resolver = Resolver::new(&state.formulae.all, &state.casks.all);
+
+        let is_tapped = |tap: &str| {
+            let tap = tap.to_lowercase();
+
+            keg::DEFAULT_TAPS.contains(&tap.as_str()) || tapped.contains(&tap)
+        };
+
+        let mut missing = Vec::new();
+
+        let installed = |kind: resolve::Kind, name: &str| match kind {
+            resolve::Kind::Formula => state.formulae.installed.contains_key(name),
+            resolve::Kind::Cask => state.casks.installed.contains_key(name),
+        };
+
+        for entry in &self.entries {
+            let (kind, resolve_kind, name) = match entry {
+                Entry::Tap(tap) => {
+                    if !is_tapped(&tap.name) {
+                        missing.push(Missing {
+                            kind: Kind::Tap,
+                            name: tap.name.clone(),
+                            known: false,
+                        });
+                    }
+
+                    continue;
+                }
+                Entry::Brew(f) => (Kind::Formula, resolve::Kind::Formula, &f.name),
+                Entry::Cask(c) => (Kind::Cask, resolve::Kind::Cask, &c.name),
+            };
+
+            let known = match resolver.resolve(name, Some(resolve_kind)) {
+                Some(resolved) if installed(resolve_kind, &resolved.name) => continue,
+                Some(_) => true,
+                None => false,
+            };
+
+            missing.push(Missing {
+                kind,
+                name: name.clone(),
+                known,
+            });
+        }
+
+        missing
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::Tap => f.write_str("tap"),
+            Kind::Formula => f.write_str("formula"),
+            Kind::Cask => f.write_str("cask"),
+        }
+    }
+}
+
+impl Formula {
+    /// Arguments of `brew install`, e.g. `--HEAD`
+    pub fn install_args(&self) -> Vec<String> {
+        self.args
+            .iter()
+            .map(|arg| {
+                if arg.starts_with('-') {
+                    arg.clone()
+                } else {
+                    format!("--{arg}")
+                }
+            })
+            .collect()
+    }
+}
+
+impl Display for Brewfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{entry}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Entry::Tap(tap) => {
+                write!(f, "tap {}", quote(&tap.name))?;
+
+                if let Some(url) = &tap.url {
+                    write!(f, ", {}", quote(url))?;
+                }
+            }
+            Entry::Brew(formula) => {
+                write!(f, "brew {}", quote(&formula.name))?;
+
+                if !formula.args.is_empty() {
+                    let args: Vec<_> = formula.args.iter().map(|a| quote(a)).collect();
+
+                    write!(f, ", args: [{}]", args.join(", "))?;
+                }
+
+                if let Some(link) = formula.link {
+                    write!(f, ", link: {link}")?;
+                }
+            }
+            Entry::Cask(cask) => write!(f, "cask {}", quote(&cask.name))?,
+        }
+
+        Ok(())
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl FromStr for Brewfile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Brewfile> {
+        let mut brewfile = Brewfile::default();
+
+        for (index, line) in s.lines().enumerate() {
+            let number = index + 1;
+
+            let mut parser = Parser::new(line);
+
+            let Some(directive) = parser.directive() else {
+                continue;
+            };
+
+            let entry = match directive.as_str() {
+                "tap" | "brew" | "cask" => parser.entry(&directive),
+                _ => {
+                    brewfile.skipped.push((number, line.trim().to_string()));
+                    continue;
+                }
+            };
+
+            let entry = entry.map_err(|message| Error::Brewfile {
+                line: number,
+                message,
+            })?;
+
+            brewfile.entries.push(entry);
+        }
+
+        Ok(brewfile)
+    }
+}
+
+/// Ruby literals the entries are made of
+#[derive(Clone, Debug)]
+enum Value {
+    String(String),
+    Symbol(String),
+    Bool(bool),
+    Nil,
+    Number,
+    Array(Vec<Value>),
+    Hash,
+}
+
+impl Value {
+    fn describe(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Symbol(_) => "a symbol",
+            Value::Bool(_) => "a boolean",
+            Value::Nil => "nil",
+            Value::Number => "a number",
+            Value::Array(_) => "a list",
+            Value::Hash => "a hash",
+        }
+    }
+}
+
+/// Parser of a single line of a Brewfile
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(line: &'a str) -> Parser<'a> {
+        Parser {
+            chars: line.chars().peekable(),
+        }
+    }
+
+    /// Name of the entry, None for blank and comment lines
+    fn directive(&mut self) -> Option<String> {
+        if self.at_end() {
+            return None;
+        }
+
+        Some(self.word())
+    }
+
+    fn entry(&mut self, directive: &str) -> std::result::Result<Entry, String> {
+        let name = match self.value()? {
+            Value::String(name) => name,
+            other => {
+                return Err(format!(
+                    "expected the name of the {directive}, found {}",
+                    other.describe()
+                ))
+            }
+        };
+
+        let mut positional = Vec::new();
+        let mut options = Vec::new();
+
+        while !self.at_end() {
+            self.expect(',')?;
+
+            match self.argument()? {
+                (Some(key), value) => options.push((key, value)),
+                (None, value) => positional.push(value),
+            }
+        }
+
+        match directive {
+            "tap" => {
+                let url = match positional.as_slice() {
+                    [] => None,
+                    [Value::String(url)] => Some(url.clone()),
+                    _ => return Err(format!("expected the URL of tap {name}")),
+                };
+
+                Ok(Entry::Tap(Tap { name, url }))
+            }
+            "brew" => {
+                if !positional.is_empty() {
+                    return Err(format!("unexpected arguments of formula {name}"));
+                }
+
+                let mut formula = Formula {
+                    name,
+                    args: Vec::new(),
+                    link: None,
+                };
+
+                for (key, value) in options {
+                    match (key.as_str(), value) {
+                        ("args", Value::Array(args)) => {
+                            for arg in args {
+                                match arg {
+                                    Value::String(s) | Value::Symbol(s) => formula.args.push(s),
+                                    _ => {
+                                        return Err(format!(
+                                            "args of {} must be strings",
+                                            formula.name
+                                        ))
+                                    }
+                                }
+                            }
+                        }
+                        ("args", _) => {
+                            return Err(format!("args of {} must be a list", formula.name))
+                        }
+                        ("link", Value::Bool(link)) => formula.link = Some(link),
+                        ("link", _) => {
+                            return Err(format!("link of {} must be true or false", formula.name))
+                        }
+                        _ => {}
+                    }
+                }
+
+                Ok(Entry::Brew(formula))
+            }
+            _ => {
+                if !positional.is_empty() {
+                    return Err(format!("unexpected arguments of cask {name}"));
+                }
+
+                Ok(Entry::Cask(Cask { name }))
+            }
+        }
+    }
+
+    /// Value with the key if it is one of the `key: value` or `:key => value` options
+    fn argument(&mut self) -> std::result::Result<(Option<String>, Value), String> {
+        self.skip_whitespace();
+
+        let start = self.chars.clone();
+
+        if let Some(key) = self.key() {
+            return Ok((Some(key), self.value()?));
+        }
+
+        self.chars = start;
+
+        Ok((None, self.value()?))
+    }
+
+    /// Key of a `key: value`, `"key": value` or `:key => value` pair, None if there is none
+    fn key(&mut self) -> Option<String> {
+        let key = match self.chars.peek()? {
+            ':' => {
+                self.chars.next();
+                self.word()
+            }
+            '"' | '\'' => self.quoted().ok()?,
+            _ => {
+                let word = self.word();
+
+                return (!word.is_empty() && self.chars.next_if_eq(&':').is_some()).then_some(word);
+            }
+        };
+
+        if key.is_empty() {
+            return None;
+        }
+
+        if self.chars.next_if_eq(&':').is_some() {
+            return Some(key);
+        }
+
+        self.skip_whitespace();
+
+        (self.chars.next_if_eq(&'=').is_some() && self.chars.next_if_eq(&'>').is_some())
+            .then_some(key)
+    }
+
+    fn value(&mut self) -> std::result::Result<Value, String> {
+        self.skip_whitespace();
+
+        match self.chars.peek().copied() {
+            Some('"' | '\'') => self.quoted().map(Value::String),
+            Some(':') => {
+                self.chars.next();
+
+                match self.word() {
+                    word if word.is_empty() => Err("expected a symbol".to_string()),
+                    word => Ok(Value::Symbol(word)),
+                }
+            }
+            Some('[') => {
+                self.chars.next();
+
+                let mut items = Vec::new();
+
+                while !self.close(']', !items.is_empty())? {
+                    items.push(self.value()?);
+                }
+
+                Ok(Value::Array(items))
+            }
+            Some('{') => {
+                self.chars.next();
+
+                let mut empty = true;
+
+                while !self.close('}', !empty)? {
+                    self.skip_whitespace();
+
+                    if self.key().is_none() {
+                        return Err("expected a key".to_string());
+                    }
+
+                    self.value()?;
+                    empty = false;
+                }
+
+                Ok(Value::Hash)
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' => {
+                while self
+                    .chars
+                    .next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '.' | '_'))
+                    .is_some()
+                {}
+
+                Ok(Value::Number)
+            }
+            Some(c) => match self.word().as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                "nil" => Ok(Value::Nil),
+                "" => Err(format!("unexpected `{c}`")),
+                word => Err(format!("unexpected `{word}`")),
+            },
+            None => Err("unexpected end of line".to_string()),
+        }
+    }
+
+    /// Whether the list is closed with the given delimiter,
+    /// otherwise expects the separator before every item but the first one
+    fn close(&mut self, delimiter: char, separated: bool) -> std::result::Result<bool, String> {
+        self.skip_whitespace();
+
+        if self.chars.next_if_eq(&delimiter).is_some() {
+            return Ok(true);
+        }
+
+        if separated {
+            self.expect(',')?;
+            self.skip_whitespace();
+
+            // trailing comma
+            if self.chars.next_if_eq(&delimiter).is_some() {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn quoted(&mut self) -> std::result::Result<String, String> {
+        let Some(quote) = self.chars.next() else {
+            return Err("expected a string".to_string());
+        };
+
+        let mut s = String::new();
+
+        while let Some(c) = self.chars.next() {
+            match c {
+                '\\' => match self.chars.next() {
+                    Some('n') if quote == '"' => s.push('\n'),
+                    Some('t') if quote == '"' => s.push('\t'),
+                    // single quotes only escape the quote and the backslash, like Ruby
+                    Some(c) if quote == '\'' && c != quote && c != '\\' => {
+                        s.push('\\');
+                        s.push(c);
+                    }
+                    Some(c) => s.push(c),
+                    None => break,
+                },
+                c if c == quote => return Ok(s),
+                c => s.push(c),
+            }
+        }
+
+        Err("unterminated string".to_string())
+    }
+
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+
+        while let Some(c) = self.chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+            word.push(c);
+        }
+
+        word
+    }
+
+    fn expect(&mut self, c: char) -> std::result::Result<(), String> {
+        self.skip_whitespace();
+
+        match self.chars.next() {
+            Some(found) if found == c => Ok(()),
+            Some(found) => Err(format!("expected `{c}`, found `{found}`")),
+            None => Err(format!("expected `{c}`")),
+        }
+    }
+
+    /// Whether only whitespace and a comment are left
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+
+        matches!(self.chars.peek(), None | Some('#'))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::{Brewfile, Cask, Entry, Formula, Kind, Tap};
+    use crate::error::Error;
+    use crate::fixtures;
+    use crate::models::{cask, formula, State};
+
+    fn parse(s: &str) -> Brewfile {
+        s.parse().unwrap()
+    }
+
+    fn brew(name: &str, args: &[&str], link: Option<bool>) -> Entry {
+        Entry::Brew(Formula {
+            name: name.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            link,
+        })
+    }
+
+    /// Line and message of the parse error
+    fn error(s: &str) -> (usize, String) {
+        match s.parse::<Brewfile>() {
+            Err(Error::Brewfile { line, message }) => (line, message),
+            Err(e) => panic!("expected a Brewfile error, got {e}"),
+            Ok(_) => panic!("expected a Brewfile error"),
+        }
+    }
+
+    #[test]
+    fn strings_are_unquoted_and_unescaped() {
+        let brewfile = parse(concat!(
+            "tap 'acme/tools', 'https://example.com/acme/tools.git'\n",
+            "brew \"say \\\"hi\\\"\\tnow\"\n",
+            "brew 'C:\\dir\\it\\'s'\n",
+            "cask \"firefox\"\n",
+        ));
+
+        assert_eq!(
+            brewfile.entries,
+            vec![
+                Entry::Tap(Tap {
+                    name: "acme/tools".to_string(),
+                    url: Some("https://example.com/acme/tools.git".to_string()),
+                }),
+                brew("say \"hi\"\tnow", &[], None),
+                brew("C:\\dir\\it's", &[], None),
+                Entry::Cask(Cask {
+                    name: "firefox".to_string(),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn options_are_read_in_every_syntax() {
+        let brewfile = parse(concat!(
+            "brew \"vim\", args: [\"with-lua\", :HEAD,], link: false\n",
+            "brew \"emacs\", :args => ['HEAD'], \"link\": true, restart_service: :changed\n",
+            "cask \"iterm2\", greedy: true, args: { appdir: \"~/Applications\" }\n",
+        ));
+
+        assert_eq!(
+            brewfile.entries,
+            vec![
+                brew("vim", &["with-lua", "HEAD"], Some(false)),
+                brew("emacs", &["HEAD"], Some(true)),
+                Entry::Cask(Cask {
+                    name: "iterm2".to_string(),
+                }),
+            ]
+        );
+
+        let Entry::Brew(vim) = &brewfile.entries[0] else {
+            unreachable!()
+        };
+
+        assert_eq!(vim.install_args(), ["--with-lua", "--HEAD"]);
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_ignored() {
+        let brewfile = parse(concat!(
+            "# frozen_string_literal: true\n",
+            "\n",
+            "   # indented comment\n",
+            "brew \"wget\" # the latest\n",
+            "brew \"a#b\", link: true# no space\n",
+        ));
+
+        assert_eq!(
+            brewfile.entries,
+            vec![brew("wget", &[], None), brew("a#b", &[], Some(true))]
+        );
+        assert!(brewfile.skipped.is_empty());
+    }
+
+    #[test]
+    fn other_entries_are_skipped_with_their_line() {
+        let brewfile = parse(concat!(
+            "brew \"wget\"\n",
+            "  mas \"Xcode\", id: 497799835\n",
+            "vscode \"rust-lang.rust-analyzer\"\n",
+            "whalebrew \"whalebrew/wget\"\n",
+        ));
+
+        assert_eq!(brewfile.entries, vec![brew("wget", &[], None)]);
+        assert_eq!(
+            brewfile.skipped,
+            vec![
+                (2, "mas \"Xcode\", id: 497799835".to_string()),
+                (3, "vscode \"rust-lang.rust-analyzer\"".to_string()),
+                (4, "whalebrew \"whalebrew/wget\"".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_tell_the_line() {
+        assert_eq!(
+            error("brew \"wget\"\n\nbrew wget\n"),
+            (3, "unexpected `wget`".to_string())
+        );
+        assert_eq!(
+            error("# comment\nbrew \"wget\", link: \"yes\""),
+            (2, "link of wget must be true or false".to_string())
+        );
+        assert_eq!(
+            error("cask :firefox"),
+            (
+                1,
+                "expected the name of the cask, found a symbol".to_string()
+            )
+        );
+        assert_eq!(
+            error("tap \"acme/tools\"\nbrew \"wget"),
+            (2, "unterminated string".to_string())
+        );
+        assert_eq!(
+            error("brew \"wget\" \"curl\""),
+            (1, "expected `,`, found `\"`".to_string())
+        );
+    }
+
+    #[test]
+    fn taps_are_missing_unless_tapped_or_default() {
+        let brewfile = parse(concat!(
+            "tap \"homebrew/cask\"\n",
+            "tap \"Acme/Tools\"\n",
+            "tap \"acme/more\"\n",
+        ));
+
+        let state = State {
+            formulae: formula::State {
+                all: fixtures::formulae([]),
+                installed: formula::installed::Store::new(),
+            },
+            casks: cask::State {
+                all: fixtures::casks([]),
+                installed: cask::installed::Store::new(),
+            },
+        };
+
+        let tapped = BTreeSet::from(["acme/tools".to_string()]);
+        let missing = brewfile.missing(&state, &tapped);
+
+        assert_eq!(missing.len(), 1);
+        assert_eq!(
+            (missing[0].kind, missing[0].name.as_str()),
+            (Kind::Tap, "acme/more")
+        );
+    }
+
+    #[test]
+    fn dump_round_trips() {
+        let mut tapped = fixtures::formula("hello", &[]);
+        tapped.base.tap = "acme/tools".to_string();
+
+        let wget = fixtures::formula("wget", &["openssl@3"]);
+        let openssl = fixtures::formula("openssl@3", &[]);
+
+        let mut head =
+            fixtures::installed_formula(&fixtures::formula("neovim", &[]), "HEAD-1a2b3c", true);
+        head.receipt.source.spec = formula::receipt::Spec::Head;
+
+        let formulae: formula::installed::Store = [
+            fixtures::installed_formula(&wget, "1.0", true),
+            fixtures::installed_formula(&openssl, "1.0", false),
+            fixtures::installed_formula(&tapped, "1.0", true),
+            head,
+        ]
+        .into_iter()
+        .map(|f| (f.upstream.base.full_name(), f))
+        .collect();
+
+        let mut font = fixtures::cask("font-fira-code", "6.2");
+        font.base.tap = "homebrew/cask-fonts".to_string();
+
+        let casks: cask::installed::Store = [
+            fixtures::installed_cask(&fixtures::cask("firefox", "130.0"), &["130.0"]),
+            fixtures::installed_cask(&font, &["6.2"]),
+        ]
+        .into_iter()
+        .map(|c| (c.upstream.base.full_token(), c))
+        .collect();
+
+        let dumped = Brewfile::dump(&formulae, &casks);
+        let text = dumped.to_string();
+
+        assert_eq!(
+            text,
+            concat!(
+                "tap \"acme/tools\"\n",
+                "tap \"homebrew/cask-fonts\"\n",
+                "brew \"acme/tools/hello\"\n",
+                "brew \"neovim\", args: [\"HEAD\"]\n",
+                "brew \"wget\"\n",
+                "cask \"firefox\"\n",
+                "cask \"homebrew/cask-fonts/font-fira-code\"\n",
+            )
+        );
+
+        assert_eq!(parse(&text).entries, dumped.entries);
+    }
+}
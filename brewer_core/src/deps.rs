@@ -0,0 +1,370 @@
+use std::collections::{HashMap, HashSet};
+
+use log::debug;
+
+use crate::models::formula;
+
+/// Dependency graph of formulae.
+///
+/// Dependencies which are not present in the store are kept as leaf nodes
+/// (see [`Graph::contains`]) and cycles are tolerated: every traversal visits
+/// each node at most once.
+pub struct Graph {
+    dependencies: HashMap<String, Edges>,
+    dependents: HashMap<String, Edges>,
+}
+
+#[derive(Default)]
+struct Edges {
+    runtime: Vec<String>,
+    build: Vec<String>,
+}
+
+impl Edges {
+    fn get(&self, include_build: bool) -> impl Iterator<Item = &String> {
+        let build = if include_build {
+            self.build.as_slice()
+        } else {
+            &[]
+        };
+
+        self.runtime.iter().chain(build.iter())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    Visiting,
+    Done,
+}
+
+impl Graph {
+    pub fn new(store: &formula::Store) -> Graph {
+        let mut dependencies: HashMap<String, Edges> = HashMap::with_capacity(store.len());
+        let mut dependents: HashMap<String, Edges> = HashMap::new();
+
+        for (name, formula) in store {
+            for dependency in &formula.base.dependencies {
+                dependents
+                    .entry(dependency.clone())
+                    .or_default()
+                    .runtime
+                    .push(name.clone());
+            }
+
+            for dependency in &formula.base.build_dependencies {
+                dependents
+                    .entry(dependency.clone())
+                    .or_default()
+                    .build
+                    .push(name.clone());
+            }
+
+            dependencies.insert(
+                name.clone(),
+                Edges {
+                    runtime: formula.base.dependencies.clone(),
+                    build: formula.base.build_dependencies.clone(),
+                },
+            );
+        }
+
+        Graph {
+            dependencies,
+            dependents,
+        }
+    }
+
+    /// Whether the given formula is a known node of the graph.
+    /// Dependencies referencing unknown formulae will return false.
+    pub fn contains(&self, name: &str) -> bool {
+        self.dependencies.contains_key(name)
+    }
+
+    /// Dependencies declared by the given formula itself.
+    pub fn direct_dependencies(&self, name: &str, include_build: bool) -> Vec<&str> {
+        let Some(edges) = self.dependencies.get(name) else {
+            return Vec::new();
+        };
+
+        let mut seen = HashSet::new();
+
+        edges
+            .get(include_build)
+            .map(String::as_str)
+            .filter(|d| seen.insert(*d))
+            .collect()
+    }
+
+    /// Formulae which declare the given formula as their dependency.
+    pub fn direct_dependents(&self, name: &str, include_build: bool) -> Vec<&str> {
+        let Some(edges) = self.dependents.get(name) else {
+            return Vec::new();
+        };
+
+        let mut seen = HashSet::new();
+        let mut dependents: Vec<_> = edges
+            .get(include_build)
+            .map(String::as_str)
+            .filter(|d| seen.insert(*d))
+            .collect();
+
+        dependents.sort_unstable();
+
+        dependents
+    }
+
+    /// All transitive dependencies of the given formula ordered so that
+    /// every dependency comes before the formulae depending on it.
+    pub fn dependencies(&self, name: &str, include_build: bool) -> Vec<String> {
+        self.topological_order([name], include_build)
+            .into_iter()
+            .filter(|n| n != name)
+            .collect()
+    }
+
+    /// All formulae which transitively depend on the given formula, sorted by name.
+    pub fn dependents(&self, name: &str, include_build: bool) -> Vec<String> {
+        let mut visited: HashSet<&str> = HashSet::new();
+        let mut queue = vec![name];
+
+        while let Some(current) = queue.pop() {
+            for dependent in self.direct_dependents(current, include_build) {
+                if visited.insert(dependent) {
+                    queue.push(dependent);
+                }
+            }
+        }
+
+        visited.remove(name);
+
+        let mut dependents: Vec<_> = visited.into_iter().map(String::from).collect();
+
+        dependents.sort_unstable();
+
+        dependents
+    }
+
+    /// The given formulae together with their transitive dependencies
+    /// in the order they should be installed.
+    ///
+    /// Edges closing a cycle are ignored.
+    pub fn topological_order<'a>(
+        &'a self,
+        names: impl IntoIterator<Item = &'a str>,
+        include_build: bool,
+    ) -> Vec<String> {
+        let mut marks: HashMap<&str, Mark> = HashMap::new();
+        let mut order = Vec::new();
+
+        for name in names {
+            self.visit(name, include_build, &mut marks, &mut order);
+        }
+
+        order
+    }
+
+    /// Formulae installed as a dependency which would no longer be needed by
+    /// any installed formula once the given formulae are removed.
+    pub fn orphans(
+        &self,
+        installed: &formula::installed::Store,
+        removing: &HashSet<&str>,
+    ) -> Vec<String> {
+        let mut removed: HashSet<&str> = removing.clone();
+        let mut orphans = Vec::new();
+
+        loop {
+            let found: Vec<&str> = installed
+                .iter()
+                .filter(|(_, f)| {
+                    f.receipt.installed_as_dependency && !f.receipt.installed_on_request
+                })
+                .map(|(name, _)| name.as_str())
+                .filter(|name| !removed.contains(name))
+                .filter(|name| {
+                    let dependents: Vec<_> = self
+                        .direct_dependents(name, false)
+                        .into_iter()
+                        .filter(|d| installed.contains_key(*d))
+                        .collect();
+
+                    !dependents.is_empty() && dependents.iter().all(|d| removed.contains(d))
+                })
+                .collect();
+
+            if found.is_empty() {
+                break;
+            }
+
+            for name in found {
+                removed.insert(name);
+                orphans.push(name.to_string());
+            }
+        }
+
+        orphans.sort_unstable();
+
+        orphans
+    }
+
+    fn visit<'a>(
+        &'a self,
+        name: &'a str,
+        include_build: bool,
+        marks: &mut HashMap<&'a str, Mark>,
+        order: &mut Vec<String>,
+    ) {
+        match marks.get(name) {
+            Some(Mark::Done) => return,
+            Some(Mark::Visiting) => {
+                debug!("dependency cycle detected at {name}");
+                return;
+            }
+            None => {}
+        }
+
+        marks.insert(name, Mark::Visiting);
+
+        for dependency in self.direct_dependencies(name, include_build) {
+            self.visit(dependency, include_build, marks, order);
+        }
+
+        marks.insert(name, Mark::Done);
+        order.push(name.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::Graph;
+    use crate::fixtures::{formula, formulae, installed_formula};
+    use crate::models::formula;
+
+    fn graph() -> Graph {
+        let mut wget = formula("wget", &["openssl@3", "libidn2"]);
+        wget.base.build_dependencies = vec!["pkgconf".to_string()];
+
+        Graph::new(&formulae([
+            wget,
+            formula("curl", &["openssl@3"]),
+            formula("openssl@3", &["ca-certificates"]),
+            formula("libidn2", &["libunistring"]),
+            formula("libunistring", &[]),
+            formula("ca-certificates", &[]),
+            formula("pkgconf", &[]),
+        ]))
+    }
+
+    fn position(order: &[String], name: &str) -> usize {
+        order.iter().position(|n| n == name).unwrap()
+    }
+
+    #[test]
+    fn dependencies_come_before_their_dependents() {
+        let order = graph().dependencies("wget", false);
+
+        assert_eq!(order.len(), 4);
+        assert!(!order.contains(&"wget".to_string()));
+        assert!(position(&order, "ca-certificates") < position(&order, "openssl@3"));
+        assert!(position(&order, "libunistring") < position(&order, "libidn2"));
+    }
+
+    #[test]
+    fn build_dependencies_are_optional() {
+        let graph = graph();
+
+        assert!(!graph
+            .dependencies("wget", false)
+            .contains(&"pkgconf".to_string()));
+        assert!(graph
+            .dependencies("wget", true)
+            .contains(&"pkgconf".to_string()));
+    }
+
+    #[test]
+    fn topological_order_visits_shared_dependencies_once() {
+        let order = graph().topological_order(["wget", "curl"], false);
+
+        let unique: HashSet<_> = order.iter().collect();
+
+        assert_eq!(unique.len(), order.len());
+        assert_eq!(order.last().map(String::as_str), Some("curl"));
+        assert!(position(&order, "openssl@3") < position(&order, "wget"));
+    }
+
+    #[test]
+    fn cycles_are_broken() {
+        let graph = Graph::new(&formulae([
+            formula("a", &["b"]),
+            formula("b", &["c"]),
+            formula("c", &["a"]),
+        ]));
+
+        assert_eq!(graph.dependencies("a", false), ["c", "b"]);
+        assert_eq!(graph.topological_order(["a"], false), ["c", "b", "a"]);
+        assert_eq!(graph.dependents("a", false), ["b", "c"]);
+    }
+
+    #[test]
+    fn missing_formulae_are_leaves() {
+        let graph = Graph::new(&formulae([formula("a", &["missing"])]));
+
+        assert!(graph.contains("a"));
+        assert!(!graph.contains("missing"));
+        assert_eq!(graph.dependencies("a", false), ["missing"]);
+        assert!(graph.direct_dependencies("missing", false).is_empty());
+        assert_eq!(graph.dependents("missing", false), ["a"]);
+    }
+
+    #[test]
+    fn dependents_are_transitive_and_sorted() {
+        let graph = graph();
+
+        assert_eq!(
+            graph.dependents("ca-certificates", false),
+            ["curl", "openssl@3", "wget"]
+        );
+        assert!(graph.dependents("pkgconf", false).is_empty());
+        assert_eq!(graph.dependents("pkgconf", true), ["wget"]);
+    }
+
+    #[test]
+    fn orphans_are_dependencies_no_longer_needed() {
+        let graph = graph();
+        let all = formulae([
+            formula("wget", &["openssl@3", "libidn2"]),
+            formula("curl", &["openssl@3"]),
+            formula("openssl@3", &["ca-certificates"]),
+            formula("libidn2", &["libunistring"]),
+            formula("libunistring", &[]),
+            formula("ca-certificates", &[]),
+        ]);
+
+        let installed: formula::installed::Store = all
+            .iter()
+            .map(|(name, f)| {
+                let on_request = name == "wget" || name == "curl";
+
+                (name.clone(), installed_formula(f, "1.0", on_request))
+            })
+            .collect();
+
+        let removing = HashSet::from(["wget"]);
+
+        // openssl@3 is still needed by curl
+        assert_eq!(
+            graph.orphans(&installed, &removing),
+            ["libidn2", "libunistring"]
+        );
+
+        let removing = HashSet::from(["wget", "curl"]);
+
+        assert_eq!(
+            graph.orphans(&installed, &removing),
+            ["ca-certificates", "libidn2", "libunistring", "openssl@3"]
+        );
+    }
+}
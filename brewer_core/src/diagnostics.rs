@@ -0,0 +1,86 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+/// Problems found while scanning the installed kegs.
+///
+/// Broken kegs are left out of the installed store instead of failing the scan.
+pub type Report = Vec<Diagnostic>;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Diagnostic {
+    /// Formula name or cask token
+    pub name: String,
+    pub path: PathBuf,
+    pub problem: Problem,
+
+    /// Underlying error, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Problem {
+    /// The link in `opt` or `Caskroom` points to nothing
+    DanglingLink,
+
+    /// The keg has no `INSTALL_RECEIPT.json`
+    MissingReceipt,
+
+    /// `INSTALL_RECEIPT.json` cannot be parsed
+    MalformedReceipt,
+
+    /// The formula is installed but not known to any tap
+    UnknownFormula,
+
+    /// The cask is installed but not known to any tap
+    UnknownCask,
+
+    /// The keg name is not in a default tap and matches kegs of several other taps
+    AmbiguousName,
+}
+
+impl Diagnostic {
+    pub fn new(name: &str, path: impl Into<PathBuf>, problem: Problem) -> Diagnostic {
+        Diagnostic {
+            name: name.to_string(),
+            path: path.into(),
+            problem,
+            detail: None,
+        }
+    }
+
+    pub fn detail(mut self, detail: impl Display) -> Diagnostic {
+        self.detail = Some(detail.to_string());
+        self
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Problem::DanglingLink => "dangling link",
+            Problem::MissingReceipt => "missing install receipt",
+            Problem::MalformedReceipt => "malformed install receipt",
+            Problem::UnknownFormula => "unknown formula",
+            Problem::UnknownCask => "unknown cask",
+            Problem::AmbiguousName => "ambiguous name",
+        };
+
+        f.write_str(s)
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} {}", self.name, self.problem, self.path.display())?;
+
+        if let Some(detail) = &self.detail {
+            write!(f, " ({detail})")?;
+        }
+
+        Ok(())
+    }
+}
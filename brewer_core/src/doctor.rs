@@ -0,0 +1,269 @@
+//! Health checks of the Homebrew prefix which do not invoke brew.
+
+use std::ffi::CString;
+use std::fmt::{Display, Formatter};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::diagnostics::{Diagnostic, Problem};
+use crate::models::keg::Status;
+use crate::models::{cask, formula};
+
+/// Cache older than this is reported as stale
+pub const STALE_CACHE_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    DanglingSymlinks,
+    UnlinkedKegs,
+    UnknownCasks,
+    DeprecatedFormulae,
+    BrokenKegs,
+    StaleCache,
+    UnwritableDirectories,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Finding {
+    pub check: Check,
+    pub severity: Severity,
+    pub message: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+impl Finding {
+    fn new(check: Check, severity: Severity, message: String) -> Finding {
+        Finding {
+            check,
+            severity,
+            message,
+            path: None,
+        }
+    }
+
+    fn path(mut self, path: impl Into<PathBuf>) -> Finding {
+        self.path = Some(path.into());
+        self
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)?;
+
+        if let Some(path) = &self.path {
+            write!(f, " ({})", path.display())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Symlinks in `bin` and `opt` pointing to nothing
+pub fn dangling_symlinks(prefix: &Path) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for dir in ["bin", "opt"] {
+        for path in entries(&prefix.join(dir)) {
+            let is_symlink = path.symlink_metadata().is_ok_and(|m| m.is_symlink());
+
+            if is_symlink && !path.exists() {
+                findings.push(
+                    Finding::new(
+                        Check::DanglingSymlinks,
+                        Severity::Warning,
+                        format!("{} is a dangling symlink", file_name(&path)),
+                    )
+                    .path(path),
+                );
+            }
+        }
+    }
+
+    findings
+}
+
+/// Kegs in `Cellar` without a link in `opt`
+pub fn unlinked_kegs(prefix: &Path) -> Vec<Finding> {
+    let opt = prefix.join("opt");
+
+    entries(&prefix.join("Cellar"))
+        .into_iter()
+        .filter(|path| path.is_dir())
+        .filter(|path| opt.join(file_name(path)).symlink_metadata().is_err())
+        .map(|path| {
+            Finding::new(
+                Check::UnlinkedKegs,
+                Severity::Warning,
+                format!("{} has no link in opt", file_name(&path)),
+            )
+            .path(path)
+        })
+        .collect()
+}
+
+/// Casks in `Caskroom` which are not known to any tap
+pub fn unknown_casks(prefix: &Path, casks: &cask::Store) -> Vec<Finding> {
+    entries(&prefix.join("Caskroom"))
+        .into_iter()
+        .filter(|path| {
+            let token = file_name(path);
+
+            !casks.contains_key(&token) && !casks.values().any(|c| c.base.token == token)
+        })
+        .map(|path| {
+            Finding::new(
+                Check::UnknownCasks,
+                Severity::Warning,
+                format!("{} is not known to any tap", file_name(&path)),
+            )
+            .path(path)
+        })
+        .collect()
+}
+
+pub fn deprecated_formulae(installed: &formula::installed::Store) -> Vec<Finding> {
+    let mut findings: Vec<_> = installed
+        .values()
+        .filter_map(|f| {
+            let base = &f.upstream.base;
+
+            let (severity, state) = match base.status() {
+                Status::Active => return None,
+                Status::Deprecated(_) => (Severity::Warning, "deprecated"),
+                Status::Disabled(_) => (Severity::Error, "disabled"),
+            };
+
+            let message = match base.status().reason() {
+                Some(reason) => format!("{} is {state}: {reason}", base.name),
+                None => format!("{} is {state}", base.name),
+            };
+
+            Some(Finding::new(Check::DeprecatedFormulae, severity, message))
+        })
+        .collect();
+
+    findings.sort_by(|a, b| a.message.cmp(&b.message));
+
+    findings
+}
+
+/// Kegs skipped while scanning the prefix.
+/// Dangling links and unknown casks are covered by their own checks
+pub fn broken_kegs(report: &[Diagnostic]) -> Vec<Finding> {
+    report
+        .iter()
+        .filter(|d| !matches!(d.problem, Problem::DanglingLink | Problem::UnknownCask))
+        .map(|d| {
+            let (severity, message) = match (d.problem, &d.detail) {
+                (Problem::UnknownFormula, _) => (
+                    Severity::Warning,
+                    format!("{} is not known to any tap", d.name),
+                ),
+                (Problem::AmbiguousName, Some(detail)) => (
+                    Severity::Warning,
+                    format!("{} is ambiguous, it {detail}", d.name),
+                ),
+                (problem, Some(detail)) => {
+                    (Severity::Error, format!("{}: {problem}, {detail}", d.name))
+                }
+                (problem, None) => (Severity::Error, format!("{}: {problem}", d.name)),
+            };
+
+            Finding::new(Check::BrokenKegs, severity, message).path(&d.path)
+        })
+        .collect()
+}
+
+/// Age of the engine cache, None if it was never updated
+pub fn stale_cache(age: Option<Duration>) -> Vec<Finding> {
+    let finding = match age {
+        None => Finding::new(
+            Check::StaleCache,
+            Severity::Info,
+            "cache was never updated".to_string(),
+        ),
+        Some(age) if age > STALE_CACHE_AGE => Finding::new(
+            Check::StaleCache,
+            Severity::Warning,
+            format!(
+                "cache was updated {} days ago, run `brewer update`",
+                age.as_secs() / (24 * 60 * 60)
+            ),
+        ),
+        Some(_) => return Vec::new(),
+    };
+
+    vec![finding]
+}
+
+/// Directories brew writes to which are not writable by the current user
+pub fn unwritable_directories(prefix: &Path) -> Vec<Finding> {
+    let dirs = [
+        prefix.to_path_buf(),
+        prefix.join("bin"),
+        prefix.join("opt"),
+        prefix.join("Cellar"),
+        prefix.join("Caskroom"),
+        prefix.join("var").join("homebrew"),
+    ];
+
+    dirs.into_iter()
+        .filter(|dir| dir.is_dir() && !is_writable(dir))
+        .map(|dir| {
+            Finding::new(
+                Check::UnwritableDirectories,
+                Severity::Error,
+                "directory is not writable".to_string(),
+            )
+            .path(dir)
+        })
+        .collect()
+}
+
+/// Asks the kernel with access(2), which accounts for the owner, the groups and ACLs
+/// of the current user without touching the directory
+fn is_writable(dir: &Path) -> bool {
+    let Ok(path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+
+    // SAFETY: path is a valid NUL-terminated string which outlives the call
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+fn entries(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = dir.read_dir() else {
+        return Vec::new();
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| !file_name(p).starts_with('.'))
+        .collect();
+
+    paths.sort();
+
+    paths
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,344 @@
+//! Per formula data from sources other than the formulae definitions.
+//!
+//! Enrichments are optional, formulae are usable without them.
+
+use std::collections::HashSet;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::fetch::{self, Fetched, Validators};
+use crate::models::analytics::{self, Count, Window};
+use crate::models::{cask, formula};
+
+/// Source of data merged into the formulae
+pub trait Enricher {
+    /// Data as fetched, cached to enrich the formulae again when only the definitions change
+    type Data: Serialize + DeserializeOwned + Default + Send;
+
+    fn name(&self) -> &'static str;
+
+    /// Fetch the data unless it did not change since the cached copy
+    fn fetch(&self, cached: Option<&Validators>) -> Result<Fetched<Self::Data>>;
+
+    fn enrich(&self, formula: &mut formula::Formula, data: &Self::Data);
+
+    fn enrich_cask(&self, _cask: &mut cask::Cask, _data: &Self::Data) {}
+}
+
+/// Which enrichments are used, all of them by default
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct Enrichments {
+    pub executables: bool,
+    pub analytics: bool,
+}
+
+impl Default for Enrichments {
+    fn default() -> Self {
+        Enrichments {
+            executables: true,
+            analytics: true,
+        }
+    }
+}
+
+/// Executables provided by the formulae, from `executables.txt` of homebrew-command-not-found
+pub struct Executables {
+    pub url: String,
+}
+
+/// Install counts of the formulae and casks from the formulae.brew.sh analytics
+pub struct Analytics {
+    /// Base of the analytics URLs, e.g. `https://formulae.brew.sh/api/analytics`
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct AnalyticsData {
+    pub formulae: analytics::Store,
+    pub casks: analytics::Store,
+}
+
+/// Rankings published for every window
+#[derive(Clone, Copy)]
+enum Ranking {
+    Install,
+    InstallOnRequest,
+    CaskInstall,
+}
+
+impl Ranking {
+    const ALL: [Ranking; 3] = [
+        Ranking::Install,
+        Ranking::InstallOnRequest,
+        Ranking::CaskInstall,
+    ];
+
+    fn path(&self) -> &'static str {
+        match self {
+            Ranking::Install => "install",
+            Ranking::InstallOnRequest => "install-on-request",
+            Ranking::CaskInstall => "cask-install",
+        }
+    }
+}
+
+impl Enricher for Executables {
+    type Data = formula::Executables;
+
+    fn name(&self) -> &'static str {
+        "executables"
+    }
+
+    fn fetch(&self, cached: Option<&Validators>) -> Result<Fetched<Self::Data>> {
+        fetch::get_since(self.name(), &self.url, cached)?.try_map(|body| Ok(Self::parse(&body)))
+    }
+
+    fn enrich(&self, formula: &mut formula::Formula, data: &Self::Data) {
+        if let Some(executables) = data.get(&formula.base.name) {
+            formula.executables = executables.clone();
+        }
+    }
+}
+
+impl Executables {
+    fn parse(body: &[u8]) -> formula::Executables {
+        let body = String::from_utf8_lossy(body);
+        let mut store = formula::Executables::new();
+
+        for line in body.lines().filter(|l| !l.is_empty()) {
+            let Some((lhs, rhs)) = line.split_once(':') else {
+                continue;
+            };
+
+            let Some(index) = lhs.find('(') else {
+                continue;
+            };
+
+            let name = &lhs[..index];
+            let executables: HashSet<String> =
+                rhs.split_whitespace().map(|s| s.to_string()).collect();
+
+            store.insert(name.to_string(), executables);
+        }
+
+        store
+    }
+}
+
+impl Enricher for Analytics {
+    type Data = AnalyticsData;
+
+    fn name(&self) -> &'static str {
+        "analytics"
+    }
+
+    fn fetch(&self, cached: Option<&Validators>) -> Result<Fetched<Self::Data>> {
+        let parts = Self::parts();
+        let base = self.url.trim_end_matches('/');
+
+        let urls: Vec<_> = parts
+            .iter()
+            .map(|(ranking, window)| format!("{base}/{}/{window}.json", ranking.path()))
+            .collect();
+
+        fetch::get_all_since(self.name(), &urls, cached)?.try_map(|bodies| {
+            let mut data = AnalyticsData::default();
+
+            for ((ranking, window), body) in parts.into_iter().zip(bodies) {
+                Self::parse(&mut data, ranking, window, &body)?;
+            }
+
+            Ok(data)
+        })
+    }
+
+    fn enrich(&self, formula: &mut formula::Formula, data: &Self::Data) {
+        let base = &formula.base;
+
+        // formulae of other taps are counted by their full name
+        formula.analytics = data
+            .formulae
+            .get(&base.name)
+            .or_else(|| data.formulae.get(&format!("{}/{}", base.tap, base.name)))
+            .cloned();
+    }
+
+    fn enrich_cask(&self, cask: &mut cask::Cask, data: &Self::Data) {
+        let base = &cask.base;
+
+        cask.analytics = data
+            .casks
+            .get(&base.token)
+            .or_else(|| data.casks.get(&format!("{}/{}", base.tap, base.token)))
+            .cloned();
+    }
+}
+
+impl Analytics {
+    fn parts() -> Vec<(Ranking, Window)> {
+        Ranking::ALL
+            .into_iter()
+            .flat_map(|ranking| Window::ALL.map(|window| (ranking, window)))
+            .collect()
+    }
+
+    fn parse(
+        data: &mut AnalyticsData,
+        ranking: Ranking,
+        window: Window,
+        body: &[u8],
+    ) -> Result<()> {
+        #[derive(Deserialize)]
+        struct Item {
+            number: i64,
+
+            #[serde(alias = "formula", alias = "cask")]
+            name: String,
+
+            /// Formatted with thousands separators
+            count: String,
+        }
+
+        #[derive(Deserialize)]
+        struct Items {
+            items: Vec<Item>,
+        }
+
+        let result: Items = serde_json::from_slice(body).map_err(Error::schema("analytics"))?;
+
+        for item in result.items {
+            let count = Count {
+                rank: item.number,
+                count: item
+                    .count
+                    .replace(',', "")
+                    .parse()
+                    .map_err(|_| Error::Schema {
+                        what: "analytics",
+                        source: serde::de::Error::custom(format!(
+                            "invalid count {} of {}",
+                            item.count, item.name
+                        )),
+                    })?,
+            };
+
+            match ranking {
+                Ranking::Install => {
+                    let analytics = data.formulae.entry(item.name).or_default();
+
+                    *analytics.install.get_mut(window) = Some(count);
+                }
+                Ranking::InstallOnRequest => {
+                    let analytics = data.formulae.entry(item.name).or_default();
+
+                    *analytics.install_on_request.get_mut(window) = Some(count);
+                }
+                Ranking::CaskInstall => {
+                    let analytics = data.casks.entry(item.name).or_default();
+
+                    *analytics.install.get_mut(window) = Some(count);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Formulae without any enrichment
+pub fn plain(formulae: formula::base::Store) -> formula::Store {
+    formulae
+        .into_iter()
+        .map(|(name, base)| {
+            (
+                name,
+                formula::Formula {
+                    base,
+                    executables: HashSet::new(),
+                    analytics: None,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Casks without any enrichment
+pub fn plain_casks(casks: cask::base::Store) -> cask::Store {
+    casks
+        .into_iter()
+        .map(|(token, base)| {
+            (
+                token,
+                cask::Cask {
+                    base,
+                    analytics: None,
+                },
+            )
+        })
+        .collect()
+}
+
+pub fn apply<E: Enricher>(
+    enricher: &E,
+    formulae: &mut formula::Store,
+    casks: &mut cask::Store,
+    data: &E::Data,
+) {
+    for formula in formulae.values_mut() {
+        enricher.enrich(formula, data);
+    }
+
+    for cask in casks.values_mut() {
+        enricher.enrich_cask(cask, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Analytics, AnalyticsData, Ranking};
+    use crate::error::Error;
+    use crate::models::analytics::Window;
+
+    #[test]
+    fn counts_are_parsed_with_thousands_separators() {
+        let mut data = AnalyticsData::default();
+        let body = br#"{"items": [{"number": 1, "formula": "wget", "count": "1,203,456"}]}"#;
+
+        Analytics::parse(&mut data, Ranking::Install, Window::Days365, body).unwrap();
+
+        let count = data.formulae["wget"].install.days365.unwrap();
+
+        assert_eq!((count.rank, count.count), (1, 1_203_456));
+        assert!(data.formulae["wget"].install_on_request.days365.is_none());
+    }
+
+    #[test]
+    fn casks_have_no_install_on_request_counts() {
+        let mut data = AnalyticsData::default();
+        let body = br#"{"items": [{"number": 3, "cask": "firefox", "count": "45,678"}]}"#;
+
+        Analytics::parse(&mut data, Ranking::CaskInstall, Window::Days30, body).unwrap();
+
+        assert_eq!(data.casks["firefox"].installs(Window::Days30), Some(45_678));
+        assert!(data.casks["firefox"].install_on_request.days30.is_none());
+    }
+
+    #[test]
+    fn malformed_count_is_a_schema_error() {
+        let mut data = AnalyticsData::default();
+        let body = br#"{"items": [{"number": 1, "formula": "wget", "count": "n/a"}]}"#;
+
+        let result = Analytics::parse(&mut data, Ranking::Install, Window::Days30, body);
+
+        assert!(matches!(
+            result,
+            Err(Error::Schema {
+                what: "analytics",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,99 @@
+use std::io;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The `brew` executable could not be started
+    #[error("brew executable not found at {}", path.display())]
+    BrewMissing { path: PathBuf },
+
+    /// `brew` exited with a failure
+    #[error("`brew {command}` failed{}", exit_status(*code, stderr))]
+    Brew {
+        command: String,
+        code: Option<i32>,
+        stderr: String,
+    },
+
+    /// The Homebrew prefix or one of its directories does not exist
+    #[error("Homebrew prefix not found at {}", path.display())]
+    PrefixMissing { path: PathBuf },
+
+    /// `INSTALL_RECEIPT.json` of an installed keg cannot be parsed
+    #[error("malformed install receipt {}", path.display())]
+    Receipt {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    /// Remote data could not be downloaded
+    #[error("failed to fetch {what} from {url}")]
+    Fetch {
+        what: &'static str,
+        url: String,
+        source: reqwest::Error,
+    },
+
+    /// Data does not match the expected JSON schema
+    #[error("unexpected {what} format")]
+    Schema {
+        what: &'static str,
+        source: serde_json::Error,
+    },
+
+    /// A line of a Brewfile cannot be parsed
+    #[error("malformed Brewfile line {line}: {message}")]
+    Brewfile { line: usize, message: String },
+
+    /// A lockfile written by `brewer lock` cannot be parsed
+    #[error("malformed lockfile {}", path.display())]
+    Lockfile {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    /// The JSON API payload or its signature is invalid
+    #[error("{0}")]
+    Signature(String),
+
+    /// Something brewer needs is not set up or cannot be located
+    #[error("{0}")]
+    Config(String),
+
+    #[error("failed to access {}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+}
+
+fn exit_status(code: Option<i32>, stderr: &str) -> String {
+    let code = match code {
+        Some(code) => format!(" with exit code {code}"),
+        None => " (terminated by signal)".to_string(),
+    };
+
+    match stderr.trim().lines().last() {
+        Some(line) => format!("{code}: {line}"),
+        None => code,
+    }
+}
+
+impl Error {
+    pub(crate) fn io(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Error {
+        let path = path.into();
+
+        move |source| Error::Io { path, source }
+    }
+
+    pub(crate) fn fetch(what: &'static str, url: &str) -> impl FnOnce(reqwest::Error) -> Error {
+        let url = url.to_string();
+
+        move |source| Error::Fetch { what, url, source }
+    }
+
+    pub(crate) fn schema(what: &'static str) -> impl FnOnce(serde_json::Error) -> Error {
+        move |source| Error::Schema { what, source }
+    }
+}
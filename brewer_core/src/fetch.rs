@@ -0,0 +1,401 @@
+//! Downloads of the remote sources, conditional on the validators of the cached copy.
+//!
+//! Sources are http(s) URLs or `file://` URLs of a local mirror.
+
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+
+/// What identifies a fetched copy of a source
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+
+    /// SHA-256 of the data, catches unchanged data served without the headers
+    pub hash: Option<String>,
+
+    /// Validators of every file of a source made of several files
+    #[serde(default)]
+    pub parts: Vec<Validators>,
+}
+
+/// Outcome of fetching a source given the validators of the cached copy
+pub enum Fetched<T> {
+    /// The cached copy is still current, validators might have been refreshed
+    Unchanged(Validators),
+    Updated(T, Validators),
+}
+
+impl Validators {
+    pub(crate) fn hashed(hash: String) -> Validators {
+        Validators {
+            hash: Some(hash),
+            ..Validators::default()
+        }
+    }
+}
+
+impl<T> Fetched<T> {
+    pub fn validators(&self) -> &Validators {
+        match self {
+            Fetched::Unchanged(validators) => validators,
+            Fetched::Updated(_, validators) => validators,
+        }
+    }
+
+    /// Fetched data, None if unchanged
+    pub fn updated(self) -> Option<T> {
+        match self {
+            Fetched::Unchanged(_) => None,
+            Fetched::Updated(data, _) => Some(data),
+        }
+    }
+
+    pub fn is_updated(&self) -> bool {
+        matches!(self, Fetched::Updated(..))
+    }
+
+    pub fn try_map<U>(self, f: impl FnOnce(T) -> Result<U>) -> Result<Fetched<U>> {
+        match self {
+            Fetched::Unchanged(validators) => Ok(Fetched::Unchanged(validators)),
+            Fetched::Updated(data, validators) => Ok(Fetched::Updated(f(data)?, validators)),
+        }
+    }
+}
+
+pub(crate) fn get(what: &'static str, url: &str) -> Result<Vec<u8>> {
+    if let Some(path) = local_path(url)? {
+        return fs::read(&path).map_err(Error::io(path));
+    }
+
+    let body = reqwest::blocking::get(url)
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.bytes())
+        .map_err(Error::fetch(what, url))?;
+
+    Ok(body.to_vec())
+}
+
+/// Download the source unless the server or the hash tells it did not change
+pub(crate) fn get_since(
+    what: &'static str,
+    url: &str,
+    cached: Option<&Validators>,
+) -> Result<Fetched<Vec<u8>>> {
+    if let Some(path) = local_path(url)? {
+        let data = fs::read(&path).map_err(Error::io(path))?;
+        let validators = Validators::hashed(hash(&[&data]));
+
+        if cached.is_some_and(|c| c.hash == validators.hash) {
+            return Ok(Fetched::Unchanged(validators));
+        }
+
+        return Ok(Fetched::Updated(data, validators));
+    }
+
+    let mut request = reqwest::blocking::Client::new().get(url);
+
+    if let Some(cached) = cached {
+        if let Some(etag) = &cached.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+
+        if let Some(last_modified) = &cached.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let response = request.send().map_err(Error::fetch(what, url))?;
+
+    if let (StatusCode::NOT_MODIFIED, Some(cached)) = (response.status(), cached) {
+        return Ok(Fetched::Unchanged(cached.clone()));
+    }
+
+    let response = response
+        .error_for_status()
+        .map_err(Error::fetch(what, url))?;
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+
+    let body = response.bytes().map_err(Error::fetch(what, url))?;
+
+    let validators = Validators {
+        etag,
+        last_modified,
+        hash: Some(hash(&[&body])),
+        ..Validators::default()
+    };
+
+    if cached.is_some_and(|c| c.hash == validators.hash) {
+        return Ok(Fetched::Unchanged(validators));
+    }
+
+    Ok(Fetched::Updated(body.to_vec(), validators))
+}
+
+/// Download every file unless none of them changed since the cached copy.
+/// The files are fetched concurrently
+pub(crate) fn get_all_since(
+    what: &'static str,
+    urls: &[String],
+    cached: Option<&Validators>,
+) -> Result<Fetched<Vec<Vec<u8>>>> {
+    let cached: Vec<_> = match cached {
+        Some(cached) if cached.parts.len() == urls.len() => cached.parts.iter().map(Some).collect(),
+        _ => vec![None; urls.len()],
+    };
+
+    let parts = thread::scope(|s| {
+        let handles: Vec<_> = urls
+            .iter()
+            .zip(cached)
+            .map(|(url, cached)| s.spawn(move || get_since(what, url, cached)))
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|h| {
+                h.join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    if parts.iter().all(|p| !p.is_updated()) {
+        return Ok(Fetched::Unchanged(Validators {
+            parts: parts.iter().map(|p| p.validators().clone()).collect(),
+            ..Validators::default()
+        }));
+    }
+
+    let mut bodies = Vec::with_capacity(parts.len());
+    let mut validators = Vec::with_capacity(parts.len());
+
+    for (url, part) in urls.iter().zip(parts) {
+        match part {
+            Fetched::Updated(body, v) => {
+                bodies.push(body);
+                validators.push(v);
+            }
+            // every file is needed to rebuild the data
+            Fetched::Unchanged(v) => {
+                bodies.push(get(what, url)?);
+                validators.push(v);
+            }
+        }
+    }
+
+    Ok(Fetched::Updated(
+        bodies,
+        Validators {
+            parts: validators,
+            ..Validators::default()
+        },
+    ))
+}
+
+/// Path of a `file://` URL, None for http(s) URLs
+fn local_path(url: &str) -> Result<Option<PathBuf>> {
+    let parsed = Url::parse(url).map_err(|e| Error::Config(format!("invalid URL {url}: {e}")))?;
+
+    match parsed.scheme() {
+        "http" | "https" => Ok(None),
+        "file" => parsed
+            .to_file_path()
+            .map(Some)
+            .map_err(|_| Error::Config(format!("invalid file URL {url}"))),
+        scheme => Err(Error::Config(format!(
+            "unsupported URL scheme {scheme} of {url}, use http(s) or file"
+        ))),
+    }
+}
+
+/// Hex encoded SHA-256 of the given parts
+pub(crate) fn hash(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+
+    for part in parts {
+        hasher.update(part);
+    }
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    use super::{get_all_since, get_since, hash, Fetched, Validators};
+
+    /// Local HTTP stand-in answering the given number of requests.
+    /// Returns the base URL and the headers of the received requests
+    fn serve(requests: usize, respond: fn(&str) -> String) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let mut received = Vec::new();
+
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut request = String::new();
+
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap();
+
+                    if line.is_empty() {
+                        break;
+                    }
+
+                    request.push_str(&line.to_lowercase());
+                    request.push('\n');
+                }
+
+                stream.write_all(respond(&request).as_bytes()).unwrap();
+                received.push(request);
+            }
+
+            received
+        });
+
+        (url, handle)
+    }
+
+    fn ok(headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\n{headers}content-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    fn not_modified() -> String {
+        "HTTP/1.1 304 Not Modified\r\nconnection: close\r\n\r\n".to_string()
+    }
+
+    #[test]
+    fn not_modified_keeps_the_cached_validators() {
+        let (url, server) = serve(1, |_| not_modified());
+
+        let cached = Validators {
+            last_modified: Some("Tue, 01 Oct 2024 00:00:00 GMT".to_string()),
+            hash: Some(hash(&[b"data"])),
+            ..Validators::default()
+        };
+
+        let fetched = get_since("test", &url, Some(&cached)).unwrap();
+
+        assert!(matches!(&fetched, Fetched::Unchanged(v) if *v == cached));
+
+        let requests = server.join().unwrap();
+
+        assert!(requests[0].contains("if-modified-since: tue, 01 oct 2024 00:00:00 gmt\n"));
+    }
+
+    #[test]
+    fn etag_is_recorded_and_sent_back() {
+        let (url, server) = serve(2, |request| {
+            if request.contains("if-none-match: \"v1\"\n") {
+                not_modified()
+            } else {
+                ok("etag: \"v1\"\r\n", "data")
+            }
+        });
+
+        let fetched = get_since("test", &url, None).unwrap();
+        let validators = fetched.validators().clone();
+
+        assert_eq!(validators.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(validators.hash, Some(hash(&[b"data"])));
+        assert_eq!(fetched.updated(), Some(b"data".to_vec()));
+
+        let fetched = get_since("test", &url, Some(&validators)).unwrap();
+
+        assert!(!fetched.is_updated());
+        assert_eq!(fetched.validators(), &validators);
+
+        let requests = server.join().unwrap();
+
+        assert!(!requests[0].contains("if-none-match"));
+        assert!(requests[1].contains("if-none-match: \"v1\"\n"));
+    }
+
+    #[test]
+    fn unchanged_hash_is_not_an_update() {
+        let (url, server) = serve(1, |_| ok("etag: \"v2\"\r\n", "data"));
+
+        let cached = Validators {
+            etag: Some("\"v1\"".to_string()),
+            hash: Some(hash(&[b"data"])),
+            ..Validators::default()
+        };
+
+        let fetched = get_since("test", &url, Some(&cached)).unwrap();
+
+        // the new validators are kept for the next request
+        match fetched {
+            Fetched::Unchanged(v) => {
+                assert_eq!(v.etag.as_deref(), Some("\"v2\""));
+                assert_eq!(v.hash, cached.hash);
+            }
+            Fetched::Updated(..) => panic!("expected the data to be unchanged"),
+        }
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn changed_part_refetches_every_file() {
+        let (url, server) = serve(3, |request| {
+            if request.starts_with("get /a ") {
+                ok("", "a")
+            } else if request.contains("if-none-match") {
+                not_modified()
+            } else {
+                ok("etag: \"b\"\r\n", "b")
+            }
+        });
+
+        let urls = [format!("{url}/a"), format!("{url}/b")];
+
+        let cached = Validators {
+            parts: vec![
+                Validators::hashed(hash(&[b"old"])),
+                Validators {
+                    etag: Some("\"b\"".to_string()),
+                    ..Validators::default()
+                },
+            ],
+            ..Validators::default()
+        };
+
+        let fetched = get_all_since("test", &urls, Some(&cached)).unwrap();
+
+        assert_eq!(fetched.validators().parts.len(), 2);
+        assert_eq!(fetched.updated(), Some(vec![b"a".to_vec(), b"b".to_vec()]));
+
+        server.join().unwrap();
+    }
+}
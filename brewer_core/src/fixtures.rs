@@ -0,0 +1,103 @@
+//! Formulae, casks and installed kegs for the unit tests.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::models::{cask, formula};
+
+/// Formula of `homebrew/core` with the given runtime dependencies
+pub(crate) fn formula(name: &str, dependencies: &[&str]) -> formula::Formula {
+    formula::Formula {
+        base: formula::base::Formula {
+            name: name.to_string(),
+            tap: "homebrew/core".to_string(),
+            desc: None,
+            homepage: None,
+            caveats: None,
+            build_dependencies: Vec::new(),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            deprecated: false,
+            deprecation_reason: None,
+            disabled: false,
+            disable_reason: None,
+            aliases: HashSet::new(),
+            oldnames: HashSet::new(),
+            versions: formula::base::Versions {
+                stable: "1.0".to_string(),
+                head: None,
+            },
+            revision: 0,
+        },
+        executables: HashSet::new(),
+        analytics: None,
+    }
+}
+
+/// Cask of `homebrew/cask` with the given version
+pub(crate) fn cask(token: &str, version: &str) -> cask::Cask {
+    cask::Cask {
+        base: cask::base::Cask {
+            token: token.to_string(),
+            tap: "homebrew/cask".to_string(),
+            desc: None,
+            version: version.to_string(),
+            caveats: None,
+            homepage: None,
+            deprecated: false,
+            deprecation_reason: None,
+            disabled: false,
+            disable_reason: None,
+            names: HashSet::new(),
+            old_tokens: HashSet::new(),
+        },
+        analytics: None,
+    }
+}
+
+/// Stable keg of the formula with the given version
+pub(crate) fn installed_formula(
+    upstream: &formula::Formula,
+    version: &str,
+    on_request: bool,
+) -> formula::installed::Formula {
+    formula::installed::Formula {
+        upstream: upstream.clone(),
+        receipt: formula::receipt::Receipt {
+            source: formula::receipt::Source {
+                spec: formula::receipt::Spec::Stable,
+                versions: formula::receipt::Versions {
+                    stable: version.to_string(),
+                    head: None,
+                },
+                tap: Some(upstream.base.tap.clone()),
+            },
+            installed_as_dependency: !on_request,
+            installed_on_request: on_request,
+        },
+        version: version.to_string(),
+        pinned: false,
+    }
+}
+
+pub(crate) fn installed_cask(upstream: &cask::Cask, versions: &[&str]) -> cask::installed::Cask {
+    cask::installed::Cask {
+        upstream: upstream.clone(),
+        versions: versions.iter().map(|v| v.to_string()).collect(),
+        pinned: false,
+    }
+}
+
+/// Store of the formulae by full name
+pub(crate) fn formulae(formulae: impl IntoIterator<Item = formula::Formula>) -> formula::Store {
+    formulae
+        .into_iter()
+        .map(|f| (f.base.full_name(), f))
+        .collect::<HashMap<_, _>>()
+}
+
+/// Store of the casks by full token
+pub(crate) fn casks(casks: impl IntoIterator<Item = cask::Cask>) -> cask::Store {
+    casks
+        .into_iter()
+        .map(|c| (c.base.full_token(), c))
+        .collect::<HashMap<_, _>>()
+}
@@ -1,18 +1,38 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::Debug;
 use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
-use std::process::Command;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
 
-use anyhow::anyhow;
 use derive_builder::Builder;
-use log::info;
+use log::{info, warn};
 use serde::Deserialize;
 
+use crate::api::Api;
+use crate::diagnostics::{Diagnostic, Problem};
+use crate::enrich::Enrichments;
+use crate::fetch::{Fetched, Validators};
 use crate::models::*;
 
+pub use crate::error::{Error, Result};
+
+pub mod api;
+pub mod brewfile;
+pub mod deps;
+pub mod diagnostics;
+pub mod doctor;
+pub mod enrich;
+mod error;
+pub mod fetch;
+#[cfg(test)]
+mod fixtures;
+pub mod lock;
 pub mod models;
+pub mod outdated;
+pub mod resolve;
+pub mod taps;
+pub mod version;
 
 const DEFAULT_BREW_PATH: &str = "brew";
 
@@ -30,12 +50,89 @@ const DEFAULT_BREW_PREFIX: &str = "/home/linuxbrew/.linuxbrew";
 const BREW_BIN_REGISTRY_URL: &str =
     "https://raw.githubusercontent.com/Homebrew/homebrew-command-not-found/master/executables.txt";
 
-const BREW_ANALYTICS_URL: &str = "https://formulae.brew.sh/api/analytics/install/30d.json";
+const BREW_ANALYTICS_URL: &str = "https://formulae.brew.sh/api/analytics";
+
+/// Locations of the remote data, http(s) or `file://` URLs
+#[derive(Clone, Debug)]
+pub struct Urls {
+    /// JSON API formulae, used with [`Source::Api`]
+    pub formulae: String,
+
+    /// JSON API casks, used with [`Source::Api`]
+    pub casks: String,
+
+    pub executables: String,
+    pub analytics: String,
+}
+
+impl Default for Urls {
+    fn default() -> Self {
+        Urls {
+            formulae: api::FORMULAE_URL.to_string(),
+            casks: api::CASKS_URL.to_string(),
+            executables: BREW_BIN_REGISTRY_URL.to_string(),
+            analytics: BREW_ANALYTICS_URL.to_string(),
+        }
+    }
+}
+
+impl Urls {
+    /// Files of a local mirror, laid out as `https://formulae.brew.sh/api/`
+    /// with `executables.txt` next to the JSON API files
+    pub fn mirror(dir: &Path) -> Result<Urls> {
+        let dir = dir.canonicalize().map_err(Error::io(dir))?;
+
+        let url = |path: PathBuf| {
+            reqwest::Url::from_file_path(&path)
+                .map(|url| url.to_string())
+                .map_err(|_| Error::Config(format!("invalid mirror path {}", path.display())))
+        };
+
+        Ok(Urls {
+            formulae: url(dir.join(api::FORMULAE_FILE))?,
+            casks: url(dir.join(api::CASKS_FILE))?,
+            executables: url(dir.join("executables.txt"))?,
+            analytics: url(dir.join("analytics"))?,
+        })
+    }
+}
+
+/// Where the formulae and casks definitions are loaded from
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    /// Evaluate with `brew info --eval-all`, requires a working Homebrew installation
+    #[default]
+    Brew,
+
+    /// Download the formulae.brew.sh JSON API
+    Api,
+
+    /// Read the JSON API files downloaded by Homebrew itself
+    HomebrewCache,
+}
 
 #[derive(Builder, Clone)]
 pub struct Brew {
     pub path: PathBuf,
     pub prefix: PathBuf,
+
+    #[builder(default)]
+    pub source: Source,
+
+    /// Public key to verify the JSON API payloads with.
+    /// If not set, the key shipped with Homebrew is used
+    #[builder(default)]
+    pub api_key: Option<PathBuf>,
+
+    #[builder(default = "true")]
+    pub verify_api: bool,
+
+    #[builder(default)]
+    pub urls: Urls,
+
+    #[builder(default)]
+    pub enrichments: Enrichments,
 }
 
 impl Default for Brew {
@@ -51,6 +148,11 @@ impl Default for Brew {
         Brew {
             path: DEFAULT_BREW_PATH.into(),
             prefix: prefix.into(),
+            source: Source::default(),
+            api_key: None,
+            verify_api: true,
+            urls: Urls::default(),
+            enrichments: Enrichments::default(),
         }
     }
 }
@@ -67,190 +169,358 @@ impl Brew {
         command
     }
 
-    pub fn install(&self, kegs: Vec<Keg>) -> anyhow::Result<()> {
+    /// Run `brew` attached to the terminal
+    fn status(&self, mut command: Command) -> Result<()> {
+        info!("running {:?}", command);
+
+        let mut child = command
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| self.spawn_error(e))?;
+
+        // stderr is passed through as it comes and kept to tell why brew failed
+        let mut stderr = Vec::new();
+
+        if let Some(mut pipe) = child.stderr.take() {
+            let mut buf = [0; 4096];
+
+            loop {
+                let n = match pipe.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(Error::io(&self.path)(e)),
+                };
+
+                // a closed stderr of our own must not fail the command
+                let _ = io::stderr().write_all(&buf[..n]);
+
+                stderr.extend_from_slice(&buf[..n]);
+            }
+        }
+
+        let status = child.wait().map_err(Error::io(&self.path))?;
+
+        if !status.success() {
+            return Err(Error::Brew {
+                command: Self::describe(&command),
+                code: status.code(),
+                stderr: String::from_utf8_lossy(&stderr).to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Run `brew` capturing its output
+    fn output(&self, mut command: Command) -> Result<Output> {
+        info!("running {:?}", command);
+
+        let output = command.output().map_err(|e| self.spawn_error(e))?;
+
+        if !output.status.success() {
+            return Err(Error::Brew {
+                command: Self::describe(&command),
+                code: output.status.code(),
+                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            });
+        }
+
+        Ok(output)
+    }
+
+    fn spawn_error(&self, e: io::Error) -> Error {
+        match e.kind() {
+            io::ErrorKind::NotFound => Error::BrewMissing {
+                path: self.path.clone(),
+            },
+            _ => Error::io(&self.path)(e),
+        }
+    }
+
+    fn describe(command: &Command) -> String {
+        command
+            .get_args()
+            .map(|a| a.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Read a directory of the Homebrew prefix
+    fn read_prefix_dir(&self, name: &str) -> Result<std::fs::ReadDir> {
+        let path = self.prefix.join(name);
+
+        path.read_dir().map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::PrefixMissing { path: path.clone() },
+            _ => Error::io(&path)(e),
+        })
+    }
+
+    pub fn install(&self, kegs: Vec<Keg>) -> Result<()> {
         let (formulae, casks) = split_kegs(kegs);
 
         if !formulae.is_empty() {
-            let status = self
-                .brew()
+            let mut command = self.brew();
+
+            command
                 .arg("install")
                 .arg("--formulae")
-                .args(formulae.into_iter().map(|f| f.base.name))
-                .status()?;
+                .args(formulae.into_iter().map(|f| f.base.full_name()));
 
-            if !status.success() {
-                return Err(anyhow!("failed to install formulae"));
-            }
+            self.status(command)?;
         }
 
         if !casks.is_empty() {
-            let status = self
-                .brew()
+            let mut command = self.brew();
+
+            command
                 .arg("install")
                 .arg("--casks")
-                .args(casks.into_iter().map(|c| c.base.token))
-                .status()?;
+                .args(casks.into_iter().map(|c| c.base.full_token()));
 
-            if !status.success() {
-                return Err(anyhow!("failed to install casks"));
-            }
+            self.status(command)?;
         }
 
         Ok(())
     }
 
-    pub fn uninstall(&self, kegs: Vec<Keg>) -> anyhow::Result<()> {
+    /// Install the formula passing the given arguments to `brew install`, e.g. `--HEAD`
+    pub fn install_with(&self, formula: &formula::Formula, args: &[String]) -> Result<()> {
+        let mut command = self.brew();
+
+        command
+            .arg("install")
+            .arg("--formula")
+            .args(args)
+            .arg(formula.base.full_name());
+
+        self.status(command)
+    }
+
+    /// Link the installed formula into the prefix, or unlink it
+    pub fn link(&self, formula: &formula::Formula, link: bool) -> Result<()> {
+        let mut command = self.brew();
+
+        command
+            .arg(if link { "link" } else { "unlink" })
+            .arg(formula.base.full_name());
+
+        self.status(command)
+    }
+
+    /// Add the tap, cloned from the given URL or from the GitHub repository its name points to
+    pub fn tap(&self, name: &str, url: Option<&str>) -> Result<()> {
+        let mut command = self.brew();
+
+        command.arg("tap").arg(name).args(url);
+
+        self.status(command)
+    }
+
+    pub fn uninstall(&self, kegs: Vec<Keg>, ignore_dependencies: bool) -> Result<()> {
         let (formulae, casks) = split_kegs(kegs);
 
         if !formulae.is_empty() {
-            let status = self
-                .brew()
-                .arg("uninstall")
-                .arg("--formulae")
-                .args(formulae.into_iter().map(|f| f.base.name))
-                .status()?;
+            let mut command = self.brew();
+
+            command.arg("uninstall").arg("--formulae");
 
-            if !status.success() {
-                return Err(anyhow!("failed to uninstall formulae"));
+            if ignore_dependencies {
+                command.arg("--ignore-dependencies");
             }
+
+            command.args(formulae.into_iter().map(|f| f.base.full_name()));
+
+            self.status(command)?;
         }
 
         if !casks.is_empty() {
-            let status = self
-                .brew()
+            let mut command = self.brew();
+
+            command
                 .arg("uninstall")
                 .arg("--casks")
-                .args(casks.into_iter().map(|c| c.base.token))
-                .status()?;
+                .args(casks.into_iter().map(|c| c.base.full_token()));
 
-            if !status.success() {
-                return Err(anyhow!("failed to uninstall casks"));
-            }
+            self.status(command)?;
         }
 
         Ok(())
     }
 
-    pub fn analytics(&self) -> anyhow::Result<formula::analytics::Store> {
-        let body = reqwest::blocking::get(BREW_ANALYTICS_URL)?.bytes()?;
+    pub fn upgrade(&self, kegs: Vec<Keg>) -> Result<()> {
+        let (formulae, casks) = split_kegs(kegs);
 
-        #[derive(Deserialize)]
-        struct Result {
-            pub items: Vec<formula::analytics::Formula>,
+        if !formulae.is_empty() {
+            let mut command = self.brew();
+
+            command
+                .arg("upgrade")
+                .arg("--formulae")
+                .args(formulae.into_iter().map(|f| f.base.full_name()));
+
+            self.status(command)?;
         }
 
-        let result: Result = serde_json::from_slice(body.iter().as_slice())?;
+        if !casks.is_empty() {
+            let mut command = self.brew();
 
-        let mut store = formula::analytics::Store::new();
+            command
+                .arg("upgrade")
+                .arg("--casks")
+                .args(casks.into_iter().map(|c| c.base.full_token()));
 
-        for item in result.items {
-            store.insert(item.formula.clone(), item);
+            self.status(command)?;
         }
 
-        Ok(store)
+        Ok(())
     }
 
-    pub fn executables(&self) -> anyhow::Result<formula::Executables> {
-        let body = reqwest::blocking::get(BREW_BIN_REGISTRY_URL)?.text()?;
-        let mut store = formula::Executables::new();
+    /// Formulae pinned with `brew pin`
+    pub fn pinned(&self) -> Result<HashSet<String>> {
+        let path = self.prefix.join("var").join("homebrew").join("pinned");
+
+        if !path.exists() {
+            return Ok(HashSet::new());
+        }
+
+        let mut pinned = HashSet::new();
 
-        for line in body.lines().filter(|l| !l.is_empty()) {
-            let Some((lhs, rhs)) = line.split_once(':') else {
+        for entry in path.read_dir().map_err(Error::io(&path))? {
+            let entry = entry.map_err(Error::io(&path))?;
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            if Self::is_dotfile(&name) {
                 continue;
-            };
+            }
+
+            pinned.insert(name);
+        }
+
+        Ok(pinned)
+    }
+
+    /// Taps cloned into the Homebrew repository, see [taps::tapped]
+    pub fn taps(&self) -> BTreeSet<String> {
+        taps::tapped(&self.prefix)
+    }
+
+    /// Formulae and casks of the given taps as evaluated by `brew`, whatever the source.
+    /// The JSON API only serves the default taps
+    pub fn tap_definitions(
+        &self,
+        taps: &[&str],
+    ) -> Result<State<formula::base::Store, cask::base::Store>> {
+        #[derive(Deserialize)]
+        struct TapInfo {
+            #[serde(default)]
+            formula_names: Vec<String>,
+
+            #[serde(default)]
+            cask_tokens: Vec<String>,
+        }
+
+        let mut command = self.brew();
+
+        command.arg("tap-info").arg("--json").args(taps);
+
+        let info: Vec<TapInfo> = serde_json::from_slice(&self.output(command)?.stdout)
+            .map_err(Error::schema("brew tap-info"))?;
 
-            let Some(index) = lhs.find('(') else {
+        let formulae: Vec<_> = info.iter().flat_map(|t| &t.formula_names).collect();
+        let casks: Vec<_> = info.iter().flat_map(|t| &t.cask_tokens).collect();
+
+        let mut definitions = State {
+            formulae: formula::base::Store::new(),
+            casks: cask::base::Store::new(),
+        };
+
+        for (flag, names) in [("--formulae", formulae), ("--casks", casks)] {
+            if names.is_empty() {
                 continue;
-            };
+            }
 
-            let name = &lhs[..index];
-            let executables: HashSet<String> =
-                rhs.split_whitespace().map(|s| s.to_string()).collect();
+            let mut command = self.brew();
 
-            store.insert(name.to_string(), executables);
+            command
+                .arg("info")
+                .arg(Self::JSON_FLAG)
+                .arg(flag)
+                .args(names);
+
+            let info = Self::parse_brew_info(&self.output(command)?.stdout)?;
+
+            definitions.formulae.extend(info.formulae);
+            definitions.casks.extend(info.casks);
         }
 
-        Ok(store)
+        Ok(definitions)
     }
 
-    pub fn state(&self) -> anyhow::Result<State<formula::State, cask::State>> {
-        let executables = self.executables()?;
-        let analytics = self.analytics()?;
-        let all = self.eval_all()?;
+    /// Unpin formulae pinned with `brew pin`, by their short names
+    pub fn unpin(&self, names: &[&str]) -> Result<()> {
+        let mut command = self.brew();
 
-        let all: State<formula::Store, cask::Store> = State {
-            formulae: all
-                .formulae
-                .into_iter()
-                .map(|(name, base)| {
-                    let executables = if let Some(e) = executables.get(&name) {
-                        e.clone()
-                    } else {
-                        HashSet::new()
-                    };
-
-                    let analytics = if let Some(a) = analytics.get(&name) {
-                        Some(a.clone())
-                    } else {
-                        analytics
-                            .get(format!("{}/{}", base.tap, base.name).as_str())
-                            .cloned()
-                    };
-
-                    (
-                        name,
-                        formula::Formula {
-                            base,
-                            executables,
-                            analytics,
-                        },
-                    )
-                })
-                .collect(),
-            casks: all
-                .casks
-                .into_iter()
-                .map(|(name, base)| (name, cask::Cask { base }))
-                .collect(),
-        };
+        command.arg("unpin").args(names);
+
+        self.status(command)
+    }
 
-        let installed = self.installed(&all)?;
+    pub fn executables(&self) -> enrich::Executables {
+        enrich::Executables {
+            url: self.urls.executables.clone(),
+        }
+    }
 
-        Ok(State {
-            formulae: formula::State {
-                all: all.formulae,
-                installed: installed.formulae,
-            },
-            casks: cask::State {
-                all: all.casks,
-                installed: installed.casks,
-            },
-        })
+    pub fn analytics(&self) -> enrich::Analytics {
+        enrich::Analytics {
+            url: self.urls.analytics.clone(),
+        }
     }
 
+    /// Installed formulae and casks, kegs that cannot be read are reported instead.
     pub fn installed(
         &self,
         all: &State<formula::Store, cask::Store>,
-    ) -> anyhow::Result<State<formula::installed::Store, cask::installed::Store>> {
-        let formulae = self.eval_installed_formulae(&all.formulae)?;
-        let casks = self.eval_installed_casks(&all.casks)?;
+    ) -> Result<(
+        State<formula::installed::Store, cask::installed::Store>,
+        diagnostics::Report,
+    )> {
+        let mut report = diagnostics::Report::new();
 
-        Ok(State { formulae, casks })
+        let formulae = self.eval_installed_formulae(&all.formulae, &mut report)?;
+        let casks = self.eval_installed_casks(&all.casks, &mut report)?;
+
+        Ok((State { formulae, casks }, report))
     }
 
-    fn eval_installed_casks(&self, store: &cask::Store) -> anyhow::Result<cask::installed::Store> {
+    fn eval_installed_casks(
+        &self,
+        store: &cask::Store,
+        report: &mut diagnostics::Report,
+    ) -> Result<cask::installed::Store> {
         let mut installed = cask::installed::Store::new();
-
-        for (name, versions) in self.eval_installed_casks_versions()? {
-            let Some(cask) = store.get(&name) else {
+        let short_tokens = Self::short_names(store, |c| &c.base.token);
+
+        for (name, versions) in self.eval_installed_casks_versions(report)? {
+            let path = self.prefix.join("Caskroom").join(&name);
+
+            let Some(cask) = Self::find_keg(
+                store,
+                &short_tokens,
+                &name,
+                (&path, Problem::UnknownCask),
+                report,
+            ) else {
                 continue;
             };
 
             installed.insert(
-                name,
+                cask.base.full_token(),
                 cask::installed::Cask {
                     upstream: cask.clone(),
                     versions,
+                    pinned: false,
                 },
             );
         }
@@ -258,13 +528,16 @@ impl Brew {
         Ok(installed)
     }
 
-    fn eval_installed_casks_versions(&self) -> anyhow::Result<cask::installed::VersionsStore> {
-        let caskroom = self.prefix.join("Caskroom").read_dir()?;
+    fn eval_installed_casks_versions(
+        &self,
+        report: &mut diagnostics::Report,
+    ) -> Result<cask::installed::VersionsStore> {
+        let caskroom = self.read_prefix_dir("Caskroom")?;
 
         let mut store = cask::installed::VersionsStore::new();
 
         for entry in caskroom {
-            let entry = entry?;
+            let entry = entry.map_err(Error::io(self.prefix.join("Caskroom")))?;
             let path = entry.path();
 
             let Some(name) = path.file_name() else {
@@ -272,47 +545,83 @@ impl Brew {
             };
 
             let name = name.to_string_lossy().to_string();
-            let mut versions: HashSet<String> = HashSet::new();
 
-            for entry in path.canonicalize()?.read_dir()? {
-                let entry = entry?;
-                let path = entry.path();
+            if Self::is_dotfile(&name) {
+                continue;
+            }
 
-                let Some(name) = path.file_name() else {
-                    continue;
-                };
+            let Ok(target) = path.canonicalize() else {
+                report.push(Diagnostic::new(&name, &path, Problem::DanglingLink));
+                continue;
+            };
 
-                let name = name.to_string_lossy().to_string();
+            let versions = Self::eval_cask_versions(&target)?;
 
-                if Self::is_dotfile(&name) {
-                    continue;
-                }
+            store.insert(name, versions);
+        }
+
+        Ok(store)
+    }
 
-                versions.insert(name);
+    /// Versions installed in the Caskroom directory of a cask
+    fn eval_cask_versions(dir: &Path) -> Result<HashSet<String>> {
+        let mut versions: HashSet<String> = HashSet::new();
+
+        for entry in dir.read_dir().map_err(Error::io(dir))? {
+            let entry = entry.map_err(Error::io(dir))?;
+            let path = entry.path();
+
+            let Some(name) = path.file_name() else {
+                continue;
+            };
+
+            let name = name.to_string_lossy().to_string();
+
+            if Self::is_dotfile(&name) {
+                continue;
             }
 
-            store.insert(name, versions);
+            versions.insert(name);
         }
 
-        Ok(store)
+        Ok(versions)
     }
 
     fn eval_installed_formulae(
         &self,
         store: &formula::Store,
-    ) -> anyhow::Result<formula::installed::Store> {
+        report: &mut diagnostics::Report,
+    ) -> Result<formula::installed::Store> {
         let mut installed = formula::installed::Store::new();
+        let short_names = Self::short_names(store, |f| &f.base.name);
+
+        for (name, (version, receipt)) in self.eval_installed_formulae_receipts(report)? {
+            let formula = match &receipt.source.tap {
+                Some(tap) => store.get(&keg::full_name(tap, &name)),
+                None => None,
+            };
 
-        for (name, receipt) in self.eval_installed_formulae_receipts()? {
-            let Some(formula) = store.get(&name) else {
+            let path = self.prefix.join("opt").join(&name);
+
+            let Some(formula) = formula.or_else(|| {
+                Self::find_keg(
+                    store,
+                    &short_names,
+                    &name,
+                    (&path, Problem::UnknownFormula),
+                    report,
+                )
+            }) else {
                 continue;
             };
 
             installed.insert(
-                name,
+                formula.base.full_name(),
                 formula::installed::Formula {
                     upstream: formula.clone(),
                     receipt,
+                    version,
+                    pinned: false,
                 },
             );
         }
@@ -320,13 +629,64 @@ impl Brew {
         Ok(installed)
     }
 
-    fn eval_installed_formulae_receipts(&self) -> anyhow::Result<formula::receipt::Store> {
-        let opt = self.prefix.join("opt").read_dir()?;
+    /// Full names of the kegs by short name, sorted
+    fn short_names<T>(
+        store: &keg::Store<T>,
+        short_name: impl Fn(&T) -> &String,
+    ) -> HashMap<&str, Vec<&str>> {
+        let mut names: HashMap<&str, Vec<&str>> = HashMap::new();
+
+        for (full_name, keg) in store {
+            names.entry(short_name(keg)).or_default().push(full_name);
+        }
+
+        for full_names in names.values_mut() {
+            full_names.sort_unstable();
+        }
+
+        names
+    }
 
-        let mut store = formula::receipt::Store::new();
+    /// Keg with the given name, from a default tap first, then the only keg with
+    /// that short name. Unknown and ambiguous names are reported as the given problem
+    fn find_keg<'a, T>(
+        store: &'a keg::Store<T>,
+        short_names: &HashMap<&str, Vec<&str>>,
+        name: &str,
+        (path, unknown): (&Path, Problem),
+        report: &mut diagnostics::Report,
+    ) -> Option<&'a T> {
+        if let Some(keg) = store.get(name) {
+            return Some(keg);
+        }
+
+        match short_names.get(name).map(Vec::as_slice) {
+            Some([full_name]) => store.get(*full_name),
+            Some(full_names) => {
+                report.push(
+                    Diagnostic::new(name, path, Problem::AmbiguousName)
+                        .detail(format!("matches {}", full_names.join(", "))),
+                );
+                None
+            }
+            None => {
+                report.push(Diagnostic::new(name, path, unknown));
+                None
+            }
+        }
+    }
+
+    /// Receipts of the linked kegs together with their versions as found in the Cellar.
+    fn eval_installed_formulae_receipts(
+        &self,
+        report: &mut diagnostics::Report,
+    ) -> Result<keg::Store<(String, formula::receipt::Receipt)>> {
+        let opt = self.read_prefix_dir("opt")?;
+
+        let mut store = keg::Store::new();
 
         for entry in opt {
-            let entry = entry?;
+            let entry = entry.map_err(Error::io(self.prefix.join("opt")))?;
             let path = entry.path();
 
             let Some(name) = path.file_name() else {
@@ -339,58 +699,260 @@ impl Brew {
                 continue;
             }
 
-            let receipt_path = path.canonicalize()?.join("INSTALL_RECEIPT.json");
+            let Some((version, receipt)) = Self::eval_opt_link(&name, &path, report)? else {
+                continue;
+            };
+
+            store.insert(name.clone(), (version, receipt));
+        }
+
+        Ok(store)
+    }
+
+    /// Version and receipt of the keg the `opt` link points to
+    fn eval_opt_link(
+        name: &str,
+        path: &Path,
+        report: &mut diagnostics::Report,
+    ) -> Result<Option<(String, formula::receipt::Receipt)>> {
+        let Ok(keg) = path.canonicalize() else {
+            report.push(Diagnostic::new(name, path, Problem::DanglingLink));
+            return Ok(None);
+        };
+
+        let version = keg
+            .file_name()
+            .map(|v| v.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let receipt_path = keg.join("INSTALL_RECEIPT.json");
+
+        match Self::read_receipt(&receipt_path) {
+            Ok(receipt) => Ok(Some((version, receipt))),
+            Err(Error::Io { path, source }) if source.kind() == io::ErrorKind::NotFound => {
+                report.push(Diagnostic::new(name, path, Problem::MissingReceipt));
+                Ok(None)
+            }
+            Err(Error::Receipt { path, source }) => {
+                report.push(Diagnostic::new(name, path, Problem::MalformedReceipt).detail(source));
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Installed keg of the given formula, without scanning the whole prefix
+    pub fn installed_formula(
+        &self,
+        formula: &formula::Formula,
+    ) -> Result<Option<formula::installed::Formula>> {
+        let path = self.prefix.join("opt").join(&formula.base.name);
 
-            let mut file = File::open(receipt_path)?;
-            let mut data = Vec::new();
+        if path.symlink_metadata().is_err() {
+            return Ok(None);
+        }
 
-            file.read_to_end(&mut data)?;
+        let mut report = diagnostics::Report::new();
 
-            let receipt: formula::receipt::Receipt = serde_json::from_slice(data.as_slice())?;
+        let installed = Self::eval_opt_link(&formula.base.name, &path, &mut report)?;
 
-            store.insert(name.clone(), receipt);
+        for diagnostic in report {
+            warn!("{diagnostic}");
         }
 
-        Ok(store)
+        // the link is named by the short name, the keg might be of a formula of another tap
+        let installed = installed.filter(|(_, receipt)| {
+            receipt
+                .source
+                .tap
+                .as_ref()
+                .is_none_or(|tap| *tap == formula.base.tap)
+        });
+
+        Ok(
+            installed.map(|(version, receipt)| formula::installed::Formula {
+                upstream: formula.clone(),
+                receipt,
+                version,
+                pinned: false,
+            }),
+        )
+    }
+
+    /// Installed versions of the given cask, without scanning the whole prefix
+    pub fn installed_cask(&self, cask: &cask::Cask) -> Result<Option<cask::installed::Cask>> {
+        let path = self.prefix.join("Caskroom").join(&cask.base.token);
+
+        if path.symlink_metadata().is_err() {
+            return Ok(None);
+        }
+
+        let Ok(target) = path.canonicalize() else {
+            warn!(
+                "{}",
+                Diagnostic::new(&cask.base.token, &path, Problem::DanglingLink)
+            );
+            return Ok(None);
+        };
+
+        Ok(Some(cask::installed::Cask {
+            upstream: cask.clone(),
+            versions: Self::eval_cask_versions(&target)?,
+            pinned: false,
+        }))
+    }
+
+    fn read_receipt(path: &Path) -> Result<formula::receipt::Receipt> {
+        let mut data = Vec::new();
+
+        File::open(path)
+            .and_then(|mut f| f.read_to_end(&mut data))
+            .map_err(Error::io(path))?;
+
+        serde_json::from_slice(data.as_slice()).map_err(|source| Error::Receipt {
+            path: path.to_path_buf(),
+            source,
+        })
     }
 
     fn is_dotfile(name: &str) -> bool {
         name.starts_with('.')
     }
 
-    fn eval_all(&self) -> anyhow::Result<State<formula::base::Store, cask::base::Store>> {
-        let mut command = self.brew();
+    /// Formulae and casks unless their definitions did not change since the cached copy
+    pub fn eval_all_since(
+        &self,
+        cached: Option<&Validators>,
+    ) -> Result<Fetched<State<formula::base::Store, cask::base::Store>>> {
+        self.load_definitions(cached)?
+            .try_map(|definitions| self.parse_definitions(&definitions))
+    }
 
-        let command = command.arg("info").arg("--eval-all").arg(Self::JSON_FLAG);
+    /// Definitions unless they did not change since the cached copy.
+    /// The JSON API files are requested conditionally, the other sources are
+    /// read in full and compared by their hash
+    fn load_definitions(&self, cached: Option<&Validators>) -> Result<Fetched<Definitions>> {
+        let definitions = match self.source {
+            Source::Brew => {
+                let mut command = self.brew();
 
-        info!("running {:?}", command);
+                command.arg("info").arg("--eval-all").arg(Self::JSON_FLAG);
+
+                Definitions::Brew(self.output(command)?.stdout)
+            }
+            Source::Api => {
+                let urls = [self.urls.formulae.clone(), self.urls.casks.clone()];
+
+                return fetch::get_all_since("definitions", &urls, cached)?.try_map(|bodies| {
+                    let mut bodies = bodies.into_iter();
+
+                    Ok(Definitions::Api {
+                        formulae: bodies.next().unwrap_or_default(),
+                        casks: bodies.next().unwrap_or_default(),
+                    })
+                });
+            }
+            Source::HomebrewCache => {
+                let Some(dir) = Api::homebrew_cache_dir() else {
+                    return Err(Error::Config(
+                        "failed to locate the Homebrew cache, set HOMEBREW_CACHE".to_string(),
+                    ));
+                };
+
+                let read = |name: &str| {
+                    let path = dir.join(name);
+
+                    std::fs::read(&path).map_err(Error::io(&path))
+                };
+
+                Definitions::Api {
+                    formulae: read(api::FORMULAE_FILE)?,
+                    casks: read(api::CASKS_FILE)?,
+                }
+            }
+        };
+
+        let validators = Validators::hashed(match &definitions {
+            Definitions::Brew(info) => fetch::hash(&[info]),
+            Definitions::Api { formulae, casks } => fetch::hash(&[formulae, casks]),
+        });
+
+        if cached.is_some_and(|c| c.hash == validators.hash) {
+            return Ok(Fetched::Unchanged(validators));
+        }
+
+        Ok(Fetched::Updated(definitions, validators))
+    }
+
+    fn parse_definitions(
+        &self,
+        definitions: &Definitions,
+    ) -> Result<State<formula::base::Store, cask::base::Store>> {
+        match definitions {
+            Definitions::Brew(info) => Self::parse_brew_info(info),
+            Definitions::Api { formulae, casks } => {
+                let api = self.api()?;
+
+                Ok(State {
+                    formulae: api.formulae(formulae)?,
+                    casks: api.casks(casks)?,
+                })
+            }
+        }
+    }
+
+    fn api(&self) -> Result<Api> {
+        if !self.verify_api {
+            return Ok(Api::new(None));
+        }
+
+        let path = match &self.api_key {
+            Some(path) => path.clone(),
+            None => Api::key_candidates(&self.prefix)
+                .into_iter()
+                .find(|p| p.exists())
+                .ok_or(Error::Config(
+                    "Homebrew public key not found, set homebrew.api_key or disable homebrew.verify_api".to_string(),
+                ))?,
+        };
 
-        let output = command.output()?;
+        Ok(Api::new(Some(Api::read_key(&path)?)))
+    }
 
+    fn parse_brew_info(info: &[u8]) -> Result<State<formula::base::Store, cask::base::Store>> {
         #[derive(Deserialize)]
-        struct Result {
+        struct Info {
             formulae: Vec<formula::base::Formula>,
             casks: Vec<cask::base::Cask>,
         }
 
-        let result: Result = serde_json::from_slice(output.stdout.as_slice())?;
+        let result: Info = serde_json::from_slice(info).map_err(Error::schema("brew info"))?;
 
         let formulae: formula::base::Store = result
             .formulae
             .into_iter()
-            .map(|f| (f.name.clone(), f))
+            .map(|f| (f.full_name(), f))
             .collect();
 
         let casks: cask::base::Store = result
             .casks
             .into_iter()
-            .map(|c| (c.token.clone(), c))
+            .map(|c| (c.full_token(), c))
             .collect();
 
         Ok(State { formulae, casks })
     }
 }
 
+/// Raw formulae and casks definitions as loaded from the source
+enum Definitions {
+    /// Output of `brew info --eval-all`
+    Brew(Vec<u8>),
+
+    /// JSON API payloads
+    Api { formulae: Vec<u8>, casks: Vec<u8> },
+}
+
 fn split_kegs(kegs: Vec<Keg>) -> (Vec<formula::Formula>, Vec<cask::Cask>) {
     let mut formulae: Vec<formula::Formula> = Vec::with_capacity(kegs.len());
     let mut casks: Vec<cask::Cask> = Vec::with_capacity(kegs.len());
@@ -404,3 +966,147 @@ fn split_kegs(kegs: Vec<Keg>) -> (Vec<formula::Formula>, Vec<cask::Cask>) {
 
     (formulae, casks)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use crate::diagnostics::{Problem, Report};
+    use crate::fixtures;
+    use crate::models::formula;
+    use crate::Brew;
+
+    fn tapped(name: &str, tap: &str) -> formula::Formula {
+        let mut formula = fixtures::formula(name, &[]);
+
+        formula.base.tap = tap.to_string();
+
+        formula
+    }
+
+    /// Full name of the keg found for the given name, with the reported problems
+    fn find(store: &formula::Store, name: &str) -> (Option<String>, Report) {
+        let short_names = Brew::short_names(store, |f| &f.base.name);
+        let mut report = Report::new();
+
+        let found = Brew::find_keg(
+            store,
+            &short_names,
+            name,
+            (Path::new("/opt"), Problem::UnknownFormula),
+            &mut report,
+        );
+
+        (found.map(|f| f.base.full_name()), report)
+    }
+
+    /// Prefix with a linked keg of the given formula, installed from the given tap
+    fn prefix(test: &str, name: &str, tap: Option<&str>) -> PathBuf {
+        let prefix = std::env::temp_dir().join(format!("brewer-{test}-{}", std::process::id()));
+        let keg = prefix.join("Cellar").join(name).join("1.0");
+
+        let _ = fs::remove_dir_all(&prefix);
+        fs::create_dir_all(&keg).unwrap();
+        fs::create_dir_all(prefix.join("opt")).unwrap();
+        std::os::unix::fs::symlink(&keg, prefix.join("opt").join(name)).unwrap();
+
+        let receipt = serde_json::json!({
+            "source": {
+                "spec": "stable",
+                "versions": { "stable": "1.0" },
+                "tap": tap,
+            },
+            "installed_as_dependency": false,
+            "installed_on_request": true,
+        });
+
+        fs::write(keg.join("INSTALL_RECEIPT.json"), receipt.to_string()).unwrap();
+
+        prefix
+    }
+
+    fn brew(prefix: PathBuf) -> Brew {
+        Brew {
+            prefix,
+            ..Brew::default()
+        }
+    }
+
+    #[test]
+    fn installed_formula_checks_the_tap_of_the_keg() {
+        let prefix = prefix("installed-formula", "hello", Some("homebrew/core"));
+        let brew = brew(prefix.clone());
+
+        let core = fixtures::formula("hello", &[]);
+        let tapped = tapped("hello", "acme/tools");
+
+        let installed = brew.installed_formula(&core).unwrap().unwrap();
+
+        assert_eq!(installed.version, "1.0");
+        assert!(brew.installed_formula(&tapped).unwrap().is_none());
+
+        fs::remove_dir_all(prefix).unwrap();
+    }
+
+    #[test]
+    fn installed_formula_without_a_tap_in_the_receipt_matches() {
+        let prefix = prefix("installed-untapped", "hello", None);
+        let brew = brew(prefix.clone());
+
+        let tapped = tapped("hello", "acme/tools");
+
+        assert!(brew.installed_formula(&tapped).unwrap().is_some());
+
+        fs::remove_dir_all(prefix).unwrap();
+    }
+
+    #[test]
+    fn default_tap_wins_over_other_taps() {
+        let store = fixtures::formulae([
+            fixtures::formula("hello", &[]),
+            tapped("hello", "acme/tools"),
+        ]);
+
+        let (found, report) = find(&store, "hello");
+
+        assert_eq!(found.as_deref(), Some("hello"));
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    fn unique_short_name_is_found() {
+        let store = fixtures::formulae([tapped("hello", "acme/tools"), tapped("bye", "acme/more")]);
+
+        let (found, report) = find(&store, "hello");
+
+        assert_eq!(found.as_deref(), Some("acme/tools/hello"));
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    fn ambiguous_short_name_is_reported() {
+        let store =
+            fixtures::formulae([tapped("hello", "acme/tools"), tapped("hello", "acme/more")]);
+
+        let (found, report) = find(&store, "hello");
+
+        assert_eq!(found, None);
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].problem, Problem::AmbiguousName);
+        assert_eq!(
+            report[0].detail.as_deref(),
+            Some("matches acme/more/hello, acme/tools/hello")
+        );
+    }
+
+    #[test]
+    fn unknown_name_is_reported() {
+        let store = fixtures::formulae([tapped("hello", "acme/tools")]);
+
+        let (found, report) = find(&store, "bye");
+
+        assert_eq!(found, None);
+        assert_eq!(report[0].problem, Problem::UnknownFormula);
+    }
+}
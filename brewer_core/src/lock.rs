@@ -0,0 +1,295 @@
+//! Lockfiles recording the exact versions of the installed formulae and casks.
+//!
+//! A lockfile is captured from a scan of the prefix and later compared with
+//! another scan to tell how the prefix drifted from it.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::models::formula::receipt::Spec;
+use crate::models::{cask, formula};
+use crate::version;
+
+/// Format of the lockfiles written by this version
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Lockfile {
+    pub version: u32,
+
+    /// Installed formulae by full name
+    pub formulae: BTreeMap<String, Formula>,
+
+    /// Installed casks by full token
+    pub casks: BTreeMap<String, Cask>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Formula {
+    /// Version of the linked keg including the revision suffix
+    pub version: String,
+    pub spec: Spec,
+    pub reason: Reason,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Cask {
+    /// Installed versions, oldest first
+    pub versions: Vec<String>,
+}
+
+/// Why a formula is installed
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    OnRequest,
+    Dependency,
+}
+
+/// Difference between the lockfile and the prefix
+#[derive(Serialize, Clone, Debug)]
+pub struct Drift {
+    #[serde(rename = "type")]
+    pub kind: Kind,
+    pub name: String,
+    pub change: Change,
+
+    /// Value in the lockfile, None if the keg is not locked
+    pub locked: Option<String>,
+
+    /// Value in the prefix, None if the keg is not installed
+    pub installed: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Formula,
+    Cask,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    /// Locked but not installed
+    Missing,
+
+    /// Installed but not locked
+    Unlocked,
+
+    Version,
+    Spec,
+    Reason,
+}
+
+impl Lockfile {
+    /// Versions, specs and install reasons of the installed formulae and casks
+    pub fn capture(
+        formulae: &formula::installed::Store,
+        casks: &cask::installed::Store,
+    ) -> Lockfile {
+        let formulae = formulae
+            .values()
+            .map(|f| {
+                let reason = if f.receipt.installed_on_request {
+                    Reason::OnRequest
+                } else {
+                    Reason::Dependency
+                };
+
+                let formula = Formula {
+                    version: f.version.clone(),
+                    spec: f.receipt.source.spec,
+                    reason,
+                };
+
+                (f.upstream.base.full_name(), formula)
+            })
+            .collect();
+
+        let casks = casks
+            .values()
+            .map(|c| {
+                let mut versions: Vec<_> = c.versions.iter().cloned().collect();
+
+                versions.sort_unstable_by(|a, b| version::compare(a, b));
+
+                (c.upstream.base.full_token(), Cask { versions })
+            })
+            .collect();
+
+        Lockfile {
+            version: FORMAT_VERSION,
+            formulae,
+            casks,
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Lockfile> {
+        let data = std::fs::read(path).map_err(Error::io(path))?;
+
+        let malformed = |source| Error::Lockfile {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        let lockfile: Lockfile = serde_json::from_slice(&data).map_err(malformed)?;
+
+        if lockfile.version != FORMAT_VERSION {
+            return Err(malformed(serde_json::Error::custom(format!(
+                "unsupported version {}, expected {FORMAT_VERSION}",
+                lockfile.version
+            ))));
+        }
+
+        Ok(lockfile)
+    }
+
+    /// How the given capture of the prefix differs from the lockfile, sorted by kind and name
+    pub fn drift(&self, current: &Lockfile) -> Vec<Drift> {
+        let mut drift = Vec::new();
+
+        for (name, change) in diff(&self.formulae, &current.formulae) {
+            let (change, locked, installed) = match change {
+                Diff::Missing(locked) => (Change::Missing, Some(&locked.version), None),
+                Diff::Unlocked(installed) => (Change::Unlocked, None, Some(&installed.version)),
+                Diff::Both(locked, installed) => {
+                    if locked.spec != installed.spec {
+                        drift.push(Drift::formula(
+                            name,
+                            Change::Spec,
+                            spec(locked.spec),
+                            spec(installed.spec),
+                        ));
+                    }
+
+                    if locked.reason != installed.reason {
+                        drift.push(Drift::formula(
+                            name,
+                            Change::Reason,
+                            reason(locked.reason),
+                            reason(installed.reason),
+                        ));
+                    }
+
+                    if locked.version == installed.version {
+                        continue;
+                    }
+
+                    (
+                        Change::Version,
+                        Some(&locked.version),
+                        Some(&installed.version),
+                    )
+                }
+            };
+
+            drift.push(Drift {
+                kind: Kind::Formula,
+                name: name.clone(),
+                change,
+                locked: locked.cloned(),
+                installed: installed.cloned(),
+            });
+        }
+
+        for (token, change) in diff(&self.casks, &current.casks) {
+            let (change, locked, installed) = match change {
+                Diff::Missing(locked) => (Change::Missing, Some(locked), None),
+                Diff::Unlocked(installed) => (Change::Unlocked, None, Some(installed)),
+                Diff::Both(locked, installed) if locked != installed => {
+                    (Change::Version, Some(locked), Some(installed))
+                }
+                Diff::Both(..) => continue,
+            };
+
+            drift.push(Drift {
+                kind: Kind::Cask,
+                name: token.clone(),
+                change,
+                locked: locked.map(|c| c.versions.join(", ")),
+                installed: installed.map(|c| c.versions.join(", ")),
+            });
+        }
+
+        drift
+    }
+}
+
+impl Display for Lockfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let json = serde_json::to_string_pretty(self).map_err(|_| std::fmt::Error)?;
+
+        writeln!(f, "{json}")
+    }
+}
+
+impl Drift {
+    fn formula(name: &str, change: Change, locked: &str, installed: &str) -> Drift {
+        Drift {
+            kind: Kind::Formula,
+            name: name.to_string(),
+            change,
+            locked: Some(locked.to_string()),
+            installed: Some(installed.to_string()),
+        }
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::Formula => f.write_str("formula"),
+            Kind::Cask => f.write_str("cask"),
+        }
+    }
+}
+
+enum Diff<'a, T> {
+    Missing(&'a T),
+    Unlocked(&'a T),
+    Both(&'a T, &'a T),
+}
+
+/// Entries of both maps by key, in key order
+fn diff<'a, T>(
+    locked: &'a BTreeMap<String, T>,
+    installed: &'a BTreeMap<String, T>,
+) -> Vec<(&'a String, Diff<'a, T>)> {
+    let mut diff: Vec<_> = locked
+        .iter()
+        .map(|(name, l)| match installed.get(name) {
+            Some(i) => (name, Diff::Both(l, i)),
+            None => (name, Diff::Missing(l)),
+        })
+        .chain(
+            installed
+                .iter()
+                .filter(|(name, _)| !locked.contains_key(*name))
+                .map(|(name, i)| (name, Diff::Unlocked(i))),
+        )
+        .collect();
+
+    diff.sort_by(|a, b| a.0.cmp(b.0));
+
+    diff
+}
+
+fn spec(spec: Spec) -> &'static str {
+    match spec {
+        Spec::Stable => "stable",
+        Spec::Head => "head",
+    }
+}
+
+fn reason(reason: Reason) -> &'static str {
+    match reason {
+        Reason::OnRequest => "on_request",
+        Reason::Dependency => "dependency",
+    }
+}
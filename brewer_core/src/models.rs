@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Clone)]
+#[derive(Deserialize, Serialize, Clone, Default)]
 pub struct State<F, C> {
     pub formulae: F,
     pub casks: C,
@@ -24,6 +24,125 @@ impl From<cask::Cask> for Keg {
     }
 }
 
+impl Keg {
+    pub fn name(&self) -> &str {
+        match self {
+            Keg::Formula(f) => &f.base.name,
+            Keg::Cask(c) => &c.base.token,
+        }
+    }
+
+    pub fn status(&self) -> keg::Status<'_> {
+        match self {
+            Keg::Formula(f) => f.base.status(),
+            Keg::Cask(c) => c.base.status(),
+        }
+    }
+
+    pub fn caveats(&self) -> Option<&str> {
+        match self {
+            Keg::Formula(f) => f.base.caveats.as_deref(),
+            Keg::Cask(c) => c.base.caveats.as_deref(),
+        }
+    }
+}
+
+pub mod analytics {
+    use std::fmt::{Display, Formatter};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::keg;
+
+    /// Analytics of the formulae or casks, by name
+    pub type Store = keg::Store<Analytics>;
+
+    /// Install counts of a formula or cask
+    #[derive(Serialize, Deserialize, Clone, Default, Debug)]
+    pub struct Analytics {
+        pub install: Windows,
+
+        /// Empty for casks, they are always installed on request
+        pub install_on_request: Windows,
+    }
+
+    /// Counts over the last 30, 90 and 365 days, None if not in the ranking
+    #[derive(Serialize, Deserialize, Clone, Default, Debug)]
+    pub struct Windows {
+        #[serde(rename = "30d")]
+        pub days30: Option<Count>,
+
+        #[serde(rename = "90d")]
+        pub days90: Option<Count>,
+
+        #[serde(rename = "365d")]
+        pub days365: Option<Count>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+    pub struct Count {
+        /// Position in the ranking, 1 being the most popular
+        pub rank: i64,
+        pub count: u64,
+    }
+
+    /// Window of the install counts
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    #[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+    pub enum Window {
+        #[default]
+        #[cfg_attr(feature = "clap", value(name = "30d"))]
+        Days30,
+
+        #[cfg_attr(feature = "clap", value(name = "90d"))]
+        Days90,
+
+        #[cfg_attr(feature = "clap", value(name = "365d"))]
+        Days365,
+    }
+
+    impl Window {
+        pub const ALL: [Window; 3] = [Window::Days30, Window::Days90, Window::Days365];
+    }
+
+    impl Display for Window {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            let s = match self {
+                Window::Days30 => "30d",
+                Window::Days90 => "90d",
+                Window::Days365 => "365d",
+            };
+
+            f.write_str(s)
+        }
+    }
+
+    impl Windows {
+        pub fn get(&self, window: Window) -> Option<&Count> {
+            match window {
+                Window::Days30 => self.days30.as_ref(),
+                Window::Days90 => self.days90.as_ref(),
+                Window::Days365 => self.days365.as_ref(),
+            }
+        }
+
+        pub fn get_mut(&mut self, window: Window) -> &mut Option<Count> {
+            match window {
+                Window::Days30 => &mut self.days30,
+                Window::Days90 => &mut self.days90,
+                Window::Days365 => &mut self.days365,
+            }
+        }
+    }
+
+    impl Analytics {
+        /// Installs in the window, None if not in the ranking
+        pub fn installs(&self, window: Window) -> Option<u64> {
+            self.install.get(window).map(|c| c.count)
+        }
+    }
+}
+
 pub mod formula {
     use std::collections::HashSet;
 
@@ -39,7 +158,7 @@ pub mod formula {
     pub struct Formula {
         pub base: base::Formula,
         pub executables: HashSet<String>,
-        pub analytics: Option<analytics::Formula>,
+        pub analytics: Option<super::analytics::Analytics>,
     }
 
     impl AsRef<str> for Formula {
@@ -79,7 +198,40 @@ pub mod formula {
             #[serde(default)]
             pub aliases: HashSet<String>,
 
+            /// Names the formula was renamed from
+            #[serde(default)]
+            pub oldnames: HashSet<String>,
+
             pub versions: Versions,
+
+            #[serde(default)]
+            pub revision: u32,
+        }
+
+        impl Formula {
+            pub fn status(&self) -> keg::Status<'_> {
+                keg::Status::new(
+                    self.deprecated,
+                    self.deprecation_reason.as_deref(),
+                    self.disabled,
+                    self.disable_reason.as_deref(),
+                )
+            }
+
+            /// Name the formula is stored by, see [keg::full_name]
+            pub fn full_name(&self) -> String {
+                keg::full_name(&self.tap, &self.name)
+            }
+
+            /// Stable version with the revision suffix, e.g. `1.2.3_1`,
+            /// as used for the keg directory names in the Cellar.
+            pub fn pkg_version(&self) -> String {
+                if self.revision == 0 {
+                    self.versions.stable.clone()
+                } else {
+                    format!("{}_{}", self.versions.stable, self.revision)
+                }
+            }
         }
 
         #[derive(Serialize, Deserialize, Clone)]
@@ -101,20 +253,13 @@ pub mod formula {
         pub struct Formula {
             pub upstream: super::Formula,
             pub receipt: receipt::Receipt,
-        }
-    }
-
-    pub mod analytics {
-        use serde::{Deserialize, Serialize};
 
-        use crate::models::keg;
-
-        pub type Store = keg::Store<Formula>;
+            /// Version of the linked keg including the revision suffix
+            pub version: String,
 
-        #[derive(Serialize, Deserialize, Clone)]
-        pub struct Formula {
-            pub number: i64,
-            pub formula: String,
+            /// Held at its version by `brewer pin` or `brew pin`
+            #[serde(default)]
+            pub pinned: bool,
         }
     }
 
@@ -136,6 +281,10 @@ pub mod formula {
         pub struct Source {
             pub spec: Spec,
             pub versions: Versions,
+
+            /// Tap the formula was installed from, missing in old receipts
+            #[serde(default)]
+            pub tap: Option<String>,
         }
 
         impl Source {
@@ -147,7 +296,7 @@ pub mod formula {
             }
         }
 
-        #[derive(Serialize, Deserialize, Clone)]
+        #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
         #[serde(rename_all = "camelCase")]
         pub enum Spec {
             Stable,
@@ -173,6 +322,7 @@ pub mod cask {
     #[derive(Serialize, Deserialize, Clone)]
     pub struct Cask {
         pub base: base::Cask,
+        pub analytics: Option<super::analytics::Analytics>,
     }
 
     impl AsRef<str> for Cask {
@@ -204,8 +354,29 @@ pub mod cask {
             pub disabled: bool,
             pub disable_reason: Option<String>,
 
-            #[serde(default)]
+            /// Display names, `name` in the Homebrew JSON
+            #[serde(default, alias = "name")]
             pub names: HashSet<String>,
+
+            /// Tokens the cask was renamed from
+            #[serde(default)]
+            pub old_tokens: HashSet<String>,
+        }
+
+        impl Cask {
+            /// Token the cask is stored by, see [keg::full_name]
+            pub fn full_token(&self) -> String {
+                keg::full_name(&self.tap, &self.token)
+            }
+
+            pub fn status(&self) -> keg::Status<'_> {
+                keg::Status::new(
+                    self.deprecated,
+                    self.deprecation_reason.as_deref(),
+                    self.disabled,
+                    self.disable_reason.as_deref(),
+                )
+            }
         }
 
         pub type State = keg::State<Cask, installed::Cask>;
@@ -226,10 +397,41 @@ pub mod cask {
         pub struct Cask {
             pub upstream: super::Cask,
             pub versions: HashSet<String>,
+
+            /// Held at its versions by `brewer pin`
+            #[serde(default)]
+            pub pinned: bool,
         }
     }
 }
 
+pub mod pin {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    /// Pinned formulae or casks, by full name
+    pub type Store = BTreeMap<String, Pin>;
+
+    pub type Pins = super::State<Store, Store>;
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct Pin {
+        pub source: Source,
+    }
+
+    /// Where a pin was made
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Source {
+        /// `brewer pin`
+        Brewer,
+
+        /// `brew pin`, imported from `var/homebrew/pinned` of the prefix
+        Homebrew,
+    }
+}
+
 pub mod keg {
     use std::collections::HashMap;
 
@@ -241,5 +443,58 @@ pub mod keg {
         pub installed: Store<Installed>,
     }
 
+    /// Formulae or casks by their full name, see [full_name]
     pub type Store<Keg> = HashMap<String, Keg>;
+
+    /// Taps whose formulae and casks are known by their short name
+    pub const DEFAULT_TAPS: [&str; 2] = ["homebrew/core", "homebrew/cask"];
+
+    /// Name unique across the taps, qualified with the tap unless it is a default one,
+    /// e.g. `wget` and `user/repo/wget`
+    pub fn full_name(tap: &str, name: &str) -> String {
+        if DEFAULT_TAPS.contains(&tap) {
+            name.to_string()
+        } else {
+            format!("{tap}/{name}")
+        }
+    }
+
+    /// Whether the formula or cask is still maintained, with the reason if it is not
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub enum Status<'a> {
+        Active,
+        Deprecated(Option<&'a str>),
+        Disabled(Option<&'a str>),
+    }
+
+    impl<'a> Status<'a> {
+        pub fn new(
+            deprecated: bool,
+            deprecation_reason: Option<&'a str>,
+            disabled: bool,
+            disable_reason: Option<&'a str>,
+        ) -> Self {
+            if disabled {
+                Status::Disabled(disable_reason)
+            } else if deprecated {
+                Status::Deprecated(deprecation_reason)
+            } else {
+                Status::Active
+            }
+        }
+
+        pub fn is_active(self) -> bool {
+            self == Status::Active
+        }
+
+        /// Reason as given by Homebrew, e.g. `does_not_build`, in a readable form
+        pub fn reason(self) -> Option<String> {
+            match self {
+                Status::Active => None,
+                Status::Deprecated(reason) | Status::Disabled(reason) => {
+                    reason.map(|r| r.replace('_', " "))
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,175 @@
+use std::cmp::Ordering;
+
+use serde::Serialize;
+
+use crate::models::{cask, formula};
+use crate::version;
+
+/// Version cask uses when it always installs the latest upstream release
+const LATEST_CASK_VERSION: &str = "latest";
+
+#[derive(Serialize, Clone)]
+pub struct Formula {
+    pub name: String,
+    pub installed: String,
+    pub current: String,
+
+    /// Pinned formulae are reported but not upgraded
+    pub pinned: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Cask {
+    pub token: String,
+    pub installed: Vec<String>,
+    pub current: String,
+
+    /// Pinned casks are reported but not upgraded
+    pub pinned: bool,
+}
+
+/// Installed formulae which have a newer version available.
+///
+/// Formulae installed from HEAD are never reported, since their upstream
+/// version can't be known without fetching the sources.
+pub fn formulae(installed: &formula::installed::Store) -> Vec<Formula> {
+    let mut outdated: Vec<_> = installed
+        .values()
+        .filter(|f| matches!(f.receipt.source.spec, formula::receipt::Spec::Stable))
+        .filter_map(|f| {
+            let current = f.upstream.base.pkg_version();
+
+            if version::compare(&f.version, &current) != Ordering::Less {
+                return None;
+            }
+
+            Some(Formula {
+                name: f.upstream.base.full_name(),
+                installed: f.version.clone(),
+                current,
+                pinned: f.pinned,
+            })
+        })
+        .collect();
+
+    outdated.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+    outdated
+}
+
+/// Installed casks none of which installed versions match the upstream one.
+///
+/// Casks with `latest` version are never reported.
+pub fn casks(installed: &cask::installed::Store) -> Vec<Cask> {
+    let mut outdated: Vec<_> = installed
+        .values()
+        .filter(|c| c.upstream.base.version != LATEST_CASK_VERSION)
+        .filter(|c| !c.versions.contains(&c.upstream.base.version))
+        .map(|c| {
+            let mut installed: Vec<_> = c.versions.iter().cloned().collect();
+
+            installed.sort_unstable_by(|a, b| version::compare(a, b));
+
+            Cask {
+                token: c.upstream.base.full_token(),
+                installed,
+                current: c.upstream.base.version.clone(),
+                pinned: c.pinned,
+            }
+        })
+        .collect();
+
+    outdated.sort_unstable_by(|a, b| a.token.cmp(&b.token));
+
+    outdated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{casks, formulae};
+    use crate::fixtures::{cask, formula, installed_cask, installed_formula};
+    use crate::models::{cask, formula};
+
+    fn installed(kegs: &[(formula::Formula, &str)]) -> formula::installed::Store {
+        kegs.iter()
+            .map(|(f, version)| (f.base.full_name(), installed_formula(f, version, true)))
+            .collect()
+    }
+
+    #[test]
+    fn formulae_behind_the_upstream_version_are_outdated() {
+        let mut wget = formula("wget", &[]);
+        wget.base.versions.stable = "1.24.5".to_string();
+        wget.base.revision = 1;
+
+        let mut curl = formula("curl", &[]);
+        curl.base.versions.stable = "8.9.0".to_string();
+
+        let outdated = formulae(&installed(&[(wget, "1.24.5"), (curl, "8.9.0")]));
+
+        assert_eq!(outdated.len(), 1);
+        assert_eq!(outdated[0].name, "wget");
+        assert_eq!(outdated[0].installed, "1.24.5");
+        assert_eq!(outdated[0].current, "1.24.5_1");
+    }
+
+    #[test]
+    fn newer_installed_formulae_are_not_outdated() {
+        let mut wget = formula("wget", &[]);
+        wget.base.versions.stable = "1.0rc1".to_string();
+
+        assert!(formulae(&installed(&[(wget, "1.0")])).is_empty());
+    }
+
+    #[test]
+    fn head_formulae_are_skipped() {
+        let mut wget = formula("wget", &[]);
+        wget.base.versions.stable = "2.0".to_string();
+
+        let mut store = installed(&[(wget, "HEAD-abc123")]);
+
+        for f in store.values_mut() {
+            f.receipt.source.spec = formula::receipt::Spec::Head;
+        }
+
+        assert!(formulae(&store).is_empty());
+    }
+
+    #[test]
+    fn casks_are_outdated_unless_any_installed_version_matches() {
+        let firefox = cask("firefox", "130.0");
+        let chrome = cask("chrome", "128.0");
+
+        let store: cask::installed::Store = [
+            (
+                "firefox".to_string(),
+                installed_cask(&firefox, &["129.0", "128.0.1"]),
+            ),
+            (
+                "chrome".to_string(),
+                installed_cask(&chrome, &["127.0", "128.0"]),
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        let outdated = casks(&store);
+
+        assert_eq!(outdated.len(), 1);
+        assert_eq!(outdated[0].token, "firefox");
+        assert_eq!(outdated[0].installed, ["128.0.1", "129.0"]);
+        assert_eq!(outdated[0].current, "130.0");
+    }
+
+    #[test]
+    fn latest_casks_are_skipped() {
+        let iterm = cask("iterm2", "latest");
+
+        let store: cask::installed::Store =
+            [("iterm2".to_string(), installed_cask(&iterm, &["3.5"]))]
+                .into_iter()
+                .collect();
+
+        assert!(casks(&store).is_empty());
+    }
+}
@@ -0,0 +1,480 @@
+//! Resolution of the names formulae and casks are known by to their canonical name.
+//!
+//! The canonical name is the full name, qualified with the tap unless the tap is a
+//! default one. Besides it a formula is known by its aliases (`python3`) and old
+//! names, a cask by its display names (`Mozilla Firefox`) and old tokens, and
+//! either by its short name. Default taps can be given too, e.g. `homebrew/core/wget`.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{cask, formula, keg};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Formula,
+    Cask,
+}
+
+/// Which other name matched
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Via {
+    Alias,
+    OldName,
+    DisplayName,
+    OldToken,
+
+    /// Name without the tap of a keg outside the default taps
+    ShortName,
+}
+
+/// Canonical name another name points to
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Target {
+    pub name: String,
+    pub via: Via,
+}
+
+/// Other names of the formulae or casks, keys are lowercase
+pub type Index = HashMap<String, Target>;
+
+#[derive(Clone, Debug)]
+pub struct Resolved {
+    pub kind: Kind,
+
+    /// Canonical formula name or cask token
+    pub name: String,
+
+    /// How the given name was matched, None if it is the canonical name
+    pub via: Option<Via>,
+
+    /// Whether the given name was qualified with the tap
+    pub qualified: bool,
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::Formula => f.write_str("formula"),
+            Kind::Cask => f.write_str("cask"),
+        }
+    }
+}
+
+impl Display for Via {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Via::Alias => "alias",
+            Via::OldName => "old name",
+            Via::DisplayName => "display name",
+            Via::OldToken => "old token",
+            Via::ShortName => "short name",
+        };
+
+        f.write_str(s)
+    }
+}
+
+impl Resolved {
+    /// Message telling which keg the given name resolved to, None if it is the canonical name
+    pub fn notice(&self, given: &str) -> Option<String> {
+        let how = match (self.via, self.qualified) {
+            (None, false) => return None,
+            (None, true) => "tap-qualified name".to_string(),
+            (Some(via), _) => via.to_string(),
+        };
+
+        Some(format!(
+            "Resolved {given} to {} {} ({how})",
+            self.kind, self.name
+        ))
+    }
+}
+
+/// Aliases, old names and short names of the formulae
+pub fn formula_index<'a>(formulae: impl Iterator<Item = &'a formula::base::Formula>) -> Index {
+    let mut names: Vec<_> = formulae
+        .flat_map(|f| {
+            let aliases = f.aliases.iter().map(|a| (a, Via::Alias));
+            let oldnames = f.oldnames.iter().map(|o| (o, Via::OldName));
+            let short = (!is_default(&f.tap)).then_some((&f.name, Via::ShortName));
+
+            aliases
+                .chain(oldnames)
+                .chain(short)
+                .map(move |(n, via)| (n, f.full_name(), via))
+        })
+        .collect();
+
+    index(&mut names)
+}
+
+/// Display names, old tokens and short tokens of the casks
+pub fn cask_index<'a>(casks: impl Iterator<Item = &'a cask::base::Cask>) -> Index {
+    let mut names: Vec<_> = casks
+        .flat_map(|c| {
+            let names = c.names.iter().map(|n| (n, Via::DisplayName));
+            let old_tokens = c.old_tokens.iter().map(|o| (o, Via::OldToken));
+            let short = (!is_default(&c.tap)).then_some((&c.token, Via::ShortName));
+
+            names
+                .chain(old_tokens)
+                .chain(short)
+                .map(move |(n, via)| (n, c.full_token(), via))
+        })
+        .collect();
+
+    index(&mut names)
+}
+
+fn is_default(tap: &str) -> bool {
+    keg::DEFAULT_TAPS.contains(&tap)
+}
+
+/// Display names are not unique, the first canonical name in order wins.
+/// Short names of kegs of several taps are left out, they have to be qualified
+fn index(names: &mut [(&String, String, Via)]) -> Index {
+    names.sort_unstable_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(b.0)));
+
+    let mut taps: HashMap<String, usize> = HashMap::new();
+
+    for (name, _, _) in names.iter().filter(|(_, _, via)| *via == Via::ShortName) {
+        *taps.entry(name.to_lowercase()).or_default() += 1;
+    }
+
+    let mut index = Index::new();
+
+    for (name, canonical, via) in names.iter() {
+        let name = name.to_lowercase();
+
+        if *via == Via::ShortName && taps[&name] > 1 {
+            continue;
+        }
+
+        index.entry(name).or_insert_with(|| Target {
+            name: canonical.to_string(),
+            via: *via,
+        });
+    }
+
+    index
+}
+
+/// Split `user/repo/name` into the tap and the name, None if the name is not qualified
+pub fn split_tap(name: &str) -> Option<(&str, &str)> {
+    let (tap, name) = name.rsplit_once('/')?;
+
+    (tap.contains('/') && !name.is_empty()).then_some((tap, name))
+}
+
+/// Resolve the name to a formula, then to a cask, unless the kind is given.
+///
+/// `tap` gives the tap of the keg with the canonical name, None if there is no such keg.
+/// `other` looks up the other names in the index of the kind, with a lowercase name
+pub fn resolve<E>(
+    name: &str,
+    kind: Option<Kind>,
+    mut tap: impl FnMut(Kind, &str) -> Result<Option<String>, E>,
+    mut other: impl FnMut(Kind, &str) -> Result<Option<Target>, E>,
+) -> Result<Option<Resolved>, E> {
+    let kinds: &[Kind] = match kind {
+        Some(Kind::Formula) => &[Kind::Formula],
+        Some(Kind::Cask) => &[Kind::Cask],
+        None => &[Kind::Formula, Kind::Cask],
+    };
+
+    let (wanted_tap, bare) = match split_tap(name) {
+        Some((tap, bare)) => (Some(tap), bare),
+        None => (None, name),
+    };
+
+    // kegs outside the default taps are stored by their qualified name
+    if wanted_tap.is_some() {
+        for &kind in kinds {
+            if tap(kind, name)?.is_some() {
+                return Ok(Some(Resolved {
+                    kind,
+                    name: name.to_string(),
+                    via: None,
+                    qualified: false,
+                }));
+            }
+        }
+    }
+
+    // canonical names take precedence over the other names of any kind
+    for &kind in kinds {
+        let Some(found) = tap(kind, bare)? else {
+            continue;
+        };
+
+        if wanted_tap.is_none_or(|t| t == found) {
+            return Ok(Some(Resolved {
+                kind,
+                name: bare.to_string(),
+                via: None,
+                qualified: wanted_tap.is_some(),
+            }));
+        }
+    }
+
+    for &kind in kinds {
+        let Some(target) = other(kind, &bare.to_lowercase())? else {
+            continue;
+        };
+
+        if let Some(wanted_tap) = wanted_tap {
+            if tap(kind, &target.name)?.as_deref() != Some(wanted_tap) {
+                continue;
+            }
+        }
+
+        return Ok(Some(Resolved {
+            kind,
+            name: target.name,
+            via: Some(target.via),
+            qualified: wanted_tap.is_some(),
+        }));
+    }
+
+    Ok(None)
+}
+
+/// Resolves names against the formulae and casks held in memory
+pub struct Resolver<'a> {
+    formulae: &'a formula::Store,
+    casks: &'a cask::Store,
+
+    formula_index: Index,
+    cask_index: Index,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(formulae: &'a formula::Store, casks: &'a cask::Store) -> Resolver<'a> {
+        Resolver {
+            formulae,
+            casks,
+            formula_index: formula_index(formulae.values().map(|f| &f.base)),
+            cask_index: cask_index(casks.values().map(|c| &c.base)),
+        }
+    }
+
+    pub fn resolve(&self, name: &str, kind: Option<Kind>) -> Option<Resolved> {
+        let tap = |kind: Kind, name: &str| {
+            let tap = match kind {
+                Kind::Formula => self.formulae.get(name).map(|f| f.base.tap.clone()),
+                Kind::Cask => self.casks.get(name).map(|c| c.base.tap.clone()),
+            };
+
+            Ok::<_, std::convert::Infallible>(tap)
+        };
+
+        let other = |kind: Kind, name: &str| {
+            let index = match kind {
+                Kind::Formula => &self.formula_index,
+                Kind::Cask => &self.cask_index,
+            };
+
+            Ok(index.get(name).cloned())
+        };
+
+        match resolve(name, kind, tap, other) {
+            Ok(resolved) => resolved,
+            Err(never) => match never {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cask_index, split_tap, Kind, Resolver, Via};
+    use crate::fixtures;
+    use crate::models::{cask, formula};
+
+    fn aliased(name: &str, tap: &str, aliases: &[&str]) -> formula::Formula {
+        let mut formula = fixtures::formula(name, &[]);
+
+        formula.base.tap = tap.to_string();
+        formula.base.aliases = aliases.iter().map(|a| a.to_string()).collect();
+
+        formula
+    }
+
+    fn named(token: &str, names: &[&str]) -> cask::Cask {
+        let mut cask = fixtures::cask(token, "1.0");
+
+        cask.base.names = names.iter().map(|n| n.to_string()).collect();
+
+        cask
+    }
+
+    /// Canonical name and how it matched, None if nothing matched
+    fn resolved(
+        resolver: &Resolver,
+        name: &str,
+        kind: Option<Kind>,
+    ) -> Option<(Kind, String, Option<Via>)> {
+        resolver
+            .resolve(name, kind)
+            .map(|r| (r.kind, r.name, r.via))
+    }
+
+    #[test]
+    fn split_tap_needs_a_qualified_name() {
+        assert_eq!(
+            split_tap("homebrew/core/wget"),
+            Some(("homebrew/core", "wget"))
+        );
+        assert_eq!(split_tap("acme/tools/hello"), Some(("acme/tools", "hello")));
+        assert_eq!(split_tap("wget"), None);
+        assert_eq!(split_tap("core/wget"), None);
+        assert_eq!(split_tap("acme/tools/"), None);
+    }
+
+    #[test]
+    fn canonical_name_wins_over_alias_for_every_kind() {
+        let formulae = fixtures::formulae([
+            fixtures::formula("python@3.12", &[]),
+            aliased("python@3.13", "homebrew/core", &["python@3.12", "firefox"]),
+        ]);
+
+        let casks = fixtures::casks([
+            named("firefox", &[]),
+            named("firefox@nightly", &["firefox", "python@3.12"]),
+        ]);
+
+        let resolver = Resolver::new(&formulae, &casks);
+
+        for kind in [None, Some(Kind::Formula)] {
+            assert_eq!(
+                resolved(&resolver, "python@3.12", kind),
+                Some((Kind::Formula, "python@3.12".to_string(), None))
+            );
+        }
+
+        // a cask by its token before a formula by its alias
+        for kind in [None, Some(Kind::Cask)] {
+            assert_eq!(
+                resolved(&resolver, "firefox", kind),
+                Some((Kind::Cask, "firefox".to_string(), None))
+            );
+        }
+
+        // the other names are used only for the given kind
+        assert_eq!(
+            resolved(&resolver, "firefox", Some(Kind::Formula)),
+            Some((Kind::Formula, "python@3.13".to_string(), Some(Via::Alias)))
+        );
+
+        assert_eq!(
+            resolved(&resolver, "python@3.12", Some(Kind::Cask)),
+            Some((
+                Kind::Cask,
+                "firefox@nightly".to_string(),
+                Some(Via::DisplayName)
+            ))
+        );
+    }
+
+    #[test]
+    fn tap_qualified_mismatch_falls_through() {
+        let formulae = fixtures::formulae([
+            fixtures::formula("hello", &[]),
+            aliased("greeter", "acme/tools", &["hello"]),
+        ]);
+
+        let casks = fixtures::casks([]);
+        let resolver = Resolver::new(&formulae, &casks);
+
+        let qualified = resolver.resolve("homebrew/core/hello", None).unwrap();
+
+        assert_eq!((qualified.name.as_str(), qualified.via), ("hello", None));
+        assert!(qualified.qualified);
+
+        // hello of homebrew/core is not in acme/tools, the alias of greeter is
+        let alias = resolver.resolve("acme/tools/hello", None).unwrap();
+
+        assert_eq!(
+            (alias.name.as_str(), alias.via),
+            ("acme/tools/greeter", Some(Via::Alias))
+        );
+        assert!(alias.qualified);
+
+        assert!(resolver.resolve("acme/other/hello", None).is_none());
+    }
+
+    #[test]
+    fn short_name_resolves_kegs_of_other_taps() {
+        let formulae = fixtures::formulae([aliased("greeter", "acme/tools", &[])]);
+        let casks = fixtures::casks([]);
+        let resolver = Resolver::new(&formulae, &casks);
+
+        assert_eq!(
+            resolved(&resolver, "Greeter", None),
+            Some((
+                Kind::Formula,
+                "acme/tools/greeter".to_string(),
+                Some(Via::ShortName)
+            ))
+        );
+
+        assert_eq!(
+            resolved(&resolver, "acme/tools/greeter", None),
+            Some((Kind::Formula, "acme/tools/greeter".to_string(), None))
+        );
+    }
+
+    #[test]
+    fn short_name_of_several_taps_is_not_resolved() {
+        let formulae = fixtures::formulae([
+            aliased("greeter", "acme/tools", &[]),
+            aliased("greeter", "acme/more", &[]),
+        ]);
+
+        let casks = fixtures::casks([]);
+        let resolver = Resolver::new(&formulae, &casks);
+
+        assert_eq!(resolved(&resolver, "greeter", None), None);
+
+        assert_eq!(
+            resolved(&resolver, "acme/more/greeter", None),
+            Some((Kind::Formula, "acme/more/greeter".to_string(), None))
+        );
+
+        // an alias of the same name is not ambiguous
+        let formulae = fixtures::formulae([
+            aliased("greeter", "acme/tools", &[]),
+            aliased("greeter", "acme/more", &[]),
+            aliased("hello", "homebrew/core", &["greeter"]),
+        ]);
+
+        let resolver = Resolver::new(&formulae, &casks);
+
+        assert_eq!(
+            resolved(&resolver, "greeter", None),
+            Some((Kind::Formula, "hello".to_string(), Some(Via::Alias)))
+        );
+    }
+
+    #[test]
+    fn first_canonical_name_wins_for_duplicate_display_names() {
+        let casks = [
+            named("visual-studio-code@insiders", &["Visual Studio Code"]),
+            named("visual-studio-code", &["Visual Studio Code"]),
+            named("vscodium", &["Visual Studio Code"]),
+        ];
+
+        // whatever the order of the casks
+        for index in [
+            cask_index(casks.iter().map(|c| &c.base)),
+            cask_index(casks.iter().rev().map(|c| &c.base)),
+        ] {
+            let target = &index["visual studio code"];
+
+            assert_eq!(target.name, "visual-studio-code");
+            assert_eq!(target.via, Via::DisplayName);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Taps the known formulae and casks come from.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::models::{cask, formula, State};
+
+#[derive(Serialize, Clone, Default)]
+pub struct Tap {
+    pub name: String,
+    pub formulae: usize,
+    pub casks: usize,
+    pub installed_formulae: usize,
+    pub installed_casks: usize,
+}
+
+/// Every tap with the number of formulae and casks it provides, sorted by name
+pub fn summary(state: &State<formula::State, cask::State>) -> Vec<Tap> {
+    let mut taps: BTreeMap<&str, Tap> = BTreeMap::new();
+
+    for f in state.formulae.all.values() {
+        tap(&mut taps, &f.base.tap).formulae += 1;
+    }
+
+    for c in state.casks.all.values() {
+        tap(&mut taps, &c.base.tap).casks += 1;
+    }
+
+    for f in state.formulae.installed.values() {
+        tap(&mut taps, &f.upstream.base.tap).installed_formulae += 1;
+    }
+
+    for c in state.casks.installed.values() {
+        tap(&mut taps, &c.upstream.base.tap).installed_casks += 1;
+    }
+
+    taps.into_values().collect()
+}
+
+fn tap<'a, 'b>(taps: &'b mut BTreeMap<&'a str, Tap>, name: &'a str) -> &'b mut Tap {
+    taps.entry(name).or_insert_with(|| Tap {
+        name: name.to_string(),
+        ..Tap::default()
+    })
+}
+
+/// Taps cloned into the Homebrew repository of the prefix, e.g. `acme/tools` for
+/// `Library/Taps/acme/homebrew-tools`. The default taps are missing when Homebrew uses the JSON API
+pub fn tapped(prefix: &Path) -> BTreeSet<String> {
+    let mut taps = BTreeSet::new();
+
+    // the repository is the prefix on Apple Silicon, a subdirectory of it elsewhere
+    for repository in [prefix.to_path_buf(), prefix.join("Homebrew")] {
+        let Ok(users) = repository.join("Library").join("Taps").read_dir() else {
+            continue;
+        };
+
+        for user in users.filter_map(|e| e.ok()) {
+            let Ok(repos) = user.path().read_dir() else {
+                continue;
+            };
+
+            let user = user.file_name().to_string_lossy().to_lowercase();
+
+            for repo in repos.filter_map(|e| e.ok()) {
+                let repo = repo.file_name().to_string_lossy().to_lowercase();
+
+                if let Some(repo) = repo.strip_prefix("homebrew-") {
+                    taps.insert(format!("{user}/{repo}"));
+                }
+            }
+        }
+    }
+
+    taps
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::tapped;
+
+    #[test]
+    fn taps_are_read_from_both_repository_layouts() {
+        let prefix = std::env::temp_dir().join(format!("brewer-tapped-{}", std::process::id()));
+
+        let _ = fs::remove_dir_all(&prefix);
+
+        for dir in [
+            "Library/Taps/acme/homebrew-tools",
+            "Library/Taps/acme/not-a-tap",
+            "Homebrew/Library/Taps/Other/homebrew-Fonts",
+        ] {
+            fs::create_dir_all(prefix.join(dir)).unwrap();
+        }
+
+        let taps: Vec<_> = tapped(&prefix).into_iter().collect();
+
+        assert_eq!(taps, ["acme/tools", "other/fonts"]);
+        assert!(tapped(&prefix.join("missing")).is_empty());
+
+        fs::remove_dir_all(prefix).unwrap();
+    }
+}
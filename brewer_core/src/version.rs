@@ -0,0 +1,119 @@
+use std::cmp::Ordering;
+
+/// Compare two Homebrew versions, e.g. `1.2.10` and `1.2.9_1`.
+///
+/// Versions are split into numeric and alphabetic components which are
+/// compared one by one. An optional `_N` revision suffix is compared last.
+pub fn compare(lhs: &str, rhs: &str) -> Ordering {
+    let (lhs, lhs_revision) = split_revision(lhs);
+    let (rhs, rhs_revision) = split_revision(rhs);
+
+    compare_components(&components(lhs), &components(rhs)).then(lhs_revision.cmp(&rhs_revision))
+}
+
+fn split_revision(version: &str) -> (&str, u32) {
+    if let Some((version, revision)) = version.rsplit_once('_') {
+        if let Ok(revision) = revision.parse() {
+            return (version, revision);
+        }
+    }
+
+    (version, 0)
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Component<'a> {
+    // alphabetic components come first, so that 1.0rc1 < 1.0
+    Alpha(&'a str),
+    Numeric(u64),
+}
+
+fn components(version: &str) -> Vec<Component<'_>> {
+    let mut components = Vec::new();
+    let mut rest = version;
+
+    while let Some(c) = rest.chars().next() {
+        if !c.is_ascii_alphanumeric() {
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        let numeric = c.is_ascii_digit();
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() || c.is_ascii_digit() != numeric)
+            .unwrap_or(rest.len());
+
+        let (component, tail) = rest.split_at(end);
+
+        components.push(match component.parse() {
+            Ok(n) if numeric => Component::Numeric(n),
+            _ => Component::Alpha(component),
+        });
+
+        rest = tail;
+    }
+
+    components
+}
+
+fn compare_components(lhs: &[Component], rhs: &[Component]) -> Ordering {
+    for i in 0..lhs.len().max(rhs.len()) {
+        let ordering = match (lhs.get(i), rhs.get(i)) {
+            (Some(l), Some(r)) => l.cmp(r),
+            // 1.0 < 1.0.1, but 1.0 > 1.0rc1
+            (Some(Component::Numeric(_)), None) => Ordering::Greater,
+            (Some(Component::Alpha(_)), None) => Ordering::Less,
+            (None, Some(Component::Numeric(_))) => Ordering::Less,
+            (None, Some(Component::Alpha(_))) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    Ordering::Equal
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering::{Equal, Greater, Less};
+
+    use super::compare;
+
+    #[test]
+    fn numeric_components_are_compared_as_numbers() {
+        assert_eq!(compare("1.2.10", "1.2.9"), Greater);
+        assert_eq!(compare("1.10", "1.9.9"), Greater);
+        assert_eq!(compare("1.2.3", "1.2.3"), Equal);
+    }
+
+    #[test]
+    fn shorter_versions_are_older() {
+        assert_eq!(compare("1.0", "1.0.1"), Less);
+        assert_eq!(compare("1.0.1", "1.0"), Greater);
+    }
+
+    #[test]
+    fn prereleases_come_before_releases() {
+        assert_eq!(compare("1.0rc1", "1.0"), Less);
+        assert_eq!(compare("1.0", "1.0rc1"), Greater);
+        assert_eq!(compare("1.0rc1", "1.0rc2"), Less);
+        assert_eq!(compare("1.0beta", "1.0rc"), Less);
+    }
+
+    #[test]
+    fn revisions_are_compared_last() {
+        assert_eq!(compare("1.24.5", "1.24.5_1"), Less);
+        assert_eq!(compare("1.24.5_2", "1.24.5_10"), Less);
+        assert_eq!(compare("1.24.6", "1.24.5_1"), Greater);
+        assert_eq!(compare("1.0_0", "1.0"), Equal);
+    }
+
+    #[test]
+    fn separators_are_ignored() {
+        assert_eq!(compare("2024-01-02", "2024.01.02"), Equal);
+        assert_eq!(compare("1.0-1", "1.0.1"), Equal);
+    }
+}
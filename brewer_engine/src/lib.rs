@@ -1,17 +1,36 @@
-use std::time::Duration;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::{Display, Formatter};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 use derive_builder::Builder;
 
-use brewer_core::{models, Brew};
-use log::info;
+use brewer_core::enrich::{self, Enricher};
+use brewer_core::fetch::Fetched;
+use brewer_core::models::pin::{self, Pin, Pins};
+use brewer_core::{doctor, models, resolve, Brew};
+use log::{debug, info, warn};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
-use crate::store::Store;
+use crate::store::{Dataset, Store};
 
 pub mod store;
 
 pub type State = models::State<models::formula::State, models::cask::State>;
 
+/// Formula together with its installed keg, if any
+pub type FormulaKeg = (
+    models::formula::Formula,
+    Option<models::formula::installed::Formula>,
+);
+
+/// Cask together with its installed versions, if any
+pub type CaskKeg = (models::cask::Cask, Option<models::cask::installed::Cask>);
+
 #[derive(Builder)]
 pub struct Engine {
     store: Store,
@@ -23,6 +42,67 @@ pub struct Engine {
     cache_duration: Option<Duration>,
 }
 
+/// What happened to a dataset during an update
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Unchanged,
+    Updated,
+
+    /// The optional dataset could not be fetched, the cached copy is kept
+    Failed(String),
+
+    /// The optional dataset is turned off in the settings
+    Disabled,
+
+    /// The optional dataset was turned off in the settings, its data is dropped
+    Dropped,
+}
+
+/// How current the data of an optional dataset is
+#[derive(Clone, Debug, PartialEq)]
+pub enum Availability {
+    Available,
+
+    /// The dataset is turned off in the settings
+    Disabled,
+
+    /// The dataset was never fetched, with the reason of the last failure
+    Unavailable(Option<String>),
+
+    /// The last fetch failed, data of an earlier fetch is used
+    Stale {
+        fetched: NaiveDateTime,
+        error: String,
+    },
+}
+
+impl Outcome {
+    /// Whether the cache changes because of the outcome
+    pub fn is_change(&self) -> bool {
+        matches!(self, Outcome::Updated | Outcome::Dropped)
+    }
+
+    fn of<T>(result: &brewer_core::Result<Fetched<T>>) -> Outcome {
+        match result {
+            Ok(fetched) if fetched.is_updated() => Outcome::Updated,
+            Ok(_) => Outcome::Unchanged,
+            Err(e) => Outcome::Failed(e.to_string()),
+        }
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Unchanged => f.write_str("unchanged"),
+            Outcome::Updated => f.write_str("updated"),
+            Outcome::Failed(reason) => write!(f, "unavailable ({reason})"),
+            Outcome::Disabled => f.write_str("disabled"),
+            Outcome::Dropped => f.write_str("disabled, dropped"),
+        }
+    }
+}
+
 impl Engine {
     pub fn new(store: Store, brew: Brew) -> Engine {
         Engine {
@@ -38,26 +118,271 @@ impl Engine {
         Ok(())
     }
 
-    pub fn uninstall(&self, kegs: Vec<models::Keg>) -> anyhow::Result<()> {
-        self.brew.uninstall(kegs)?;
+    /// Install the formula passing the given arguments to `brew install`, e.g. `--HEAD`
+    pub fn install_with(
+        &self,
+        formula: &models::formula::Formula,
+        args: &[String],
+    ) -> anyhow::Result<()> {
+        self.brew.install_with(formula, args)?;
+
+        Ok(())
+    }
+
+    pub fn link(&self, formula: &models::formula::Formula, link: bool) -> anyhow::Result<()> {
+        self.brew.link(formula, link)?;
+
+        Ok(())
+    }
+
+    /// Add the tap, formulae and casks from it are known after the next update
+    pub fn tap(&self, name: &str, url: Option<&str>) -> anyhow::Result<()> {
+        self.brew.tap(name, url)?;
+
+        Ok(())
+    }
+
+    /// Taps cloned into the Homebrew repository, whatever the cache holds
+    pub fn taps(&self) -> BTreeSet<String> {
+        self.brew.taps()
+    }
+
+    /// State extended with the formulae and casks of the given taps evaluated by `brew`,
+    /// for the taps the source does not serve. They are not cached
+    pub fn with_taps(&self, state: State, taps: &[&str]) -> anyhow::Result<State> {
+        let definitions = self.brew.tap_definitions(taps)?;
+
+        let mut all = models::State {
+            formulae: state.formulae.all,
+            casks: state.casks.all,
+        };
+
+        all.formulae.extend(enrich::plain(definitions.formulae));
+        all.casks.extend(enrich::plain_casks(definitions.casks));
+
+        self.state(all)
+    }
+
+    /// Uninstall the kegs and drop their pins
+    pub fn uninstall(
+        &mut self,
+        kegs: Vec<models::Keg>,
+        ignore_dependencies: bool,
+    ) -> anyhow::Result<()> {
+        let mut pins = self.store.get_pins()?;
+        let mut unpinned = false;
+
+        for keg in &kegs {
+            unpinned |= match keg {
+                models::Keg::Formula(f) => pins.formulae.remove(&f.base.full_name()).is_some(),
+                models::Keg::Cask(c) => pins.casks.remove(&c.base.full_token()).is_some(),
+            };
+        }
+
+        self.brew.uninstall(kegs, ignore_dependencies)?;
+
+        if unpinned {
+            self.store.set_pins(&pins)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn upgrade(&self, kegs: Vec<models::Keg>) -> anyhow::Result<()> {
+        self.brew.upgrade(kegs)?;
+
+        Ok(())
+    }
+
+    /// Pinned formulae and casks, with the Homebrew pins as last imported
+    pub fn pins(&self) -> anyhow::Result<Pins> {
+        self.store.get_pins()
+    }
+
+    /// Pin the formula or cask with the given canonical name, false if it already is
+    pub fn pin(&mut self, kind: resolve::Kind, name: &str) -> anyhow::Result<bool> {
+        self.import_pins()?;
+
+        let mut pins = self.store.get_pins()?;
+
+        let pinned = match pins_of(&mut pins, kind).entry(name.to_string()) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(Pin {
+                    source: pin::Source::Brewer,
+                });
+
+                true
+            }
+        };
+
+        if pinned {
+            self.store.set_pins(&pins)?;
+        }
+
+        Ok(pinned)
+    }
+
+    /// Unpin the formula or cask with the given canonical name, None if it is not pinned.
+    /// Formulae pinned with `brew pin` are unpinned with `brew unpin` too, otherwise
+    /// they would be imported again, the returned source is then [pin::Source::Homebrew]
+    pub fn unpin(
+        &mut self,
+        kind: resolve::Kind,
+        name: &str,
+    ) -> anyhow::Result<Option<pin::Source>> {
+        self.import_pins()?;
+
+        let mut pins = self.store.get_pins()?;
+
+        let Some(mut pin) = pins_of(&mut pins, kind).remove(name) else {
+            return Ok(None);
+        };
+
+        if kind == resolve::Kind::Formula {
+            // Homebrew pins kegs by their short name
+            let short = resolve::split_tap(name).map_or(name, |(_, short)| short);
+
+            if self.brew.pinned()?.contains(short) {
+                self.brew.unpin(&[short])?;
+                pin.source = pin::Source::Homebrew;
+            }
+        }
+
+        self.store.set_pins(&pins)?;
+
+        Ok(Some(pin.source))
+    }
+
+    /// Import the formulae pinned with `brew pin` and drop the imported pins Homebrew no longer has.
+    /// Done by every update and by the commands reading or changing the pins, see [Engine::ensure_pins],
+    /// the other commands use the pins as last imported
+    pub fn import_pins(&mut self) -> anyhow::Result<()> {
+        let mut imported = HashSet::new();
+
+        for name in self.brew.pinned()? {
+            match self.resolve(&name, Some(resolve::Kind::Formula))? {
+                Some(resolved) => {
+                    imported.insert(resolved.name);
+                }
+                None => debug!("skipping the Homebrew pin of unknown formula {name}"),
+            }
+        }
+
+        let mut pins = self.store.get_pins()?;
+        let count = pins.formulae.len();
+
+        pins.formulae
+            .retain(|name, pin| pin.source != pin::Source::Homebrew || imported.contains(name));
+
+        let mut changed = pins.formulae.len() != count;
+
+        for name in imported {
+            if let Entry::Vacant(entry) = pins.formulae.entry(name) {
+                entry.insert(Pin {
+                    source: pin::Source::Homebrew,
+                });
+
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.store.set_pins(&pins)?;
+        }
 
         Ok(())
     }
 
     pub fn cache_or_latest(&mut self) -> anyhow::Result<State> {
-        let cache = self.cache()?;
+        self.ensure_cache()?;
+
+        self.cache()?
+            .ok_or(anyhow::anyhow!("the cache is empty after the update"))
+    }
+
+    /// Update the cache if needed and import the Homebrew pins,
+    /// for the commands which tell or act on what is pinned
+    pub fn ensure_pins(&mut self) -> anyhow::Result<()> {
+        self.ensure_cache()?;
+        self.import_pins()
+    }
 
-        if self.cache_expired()? || cache.is_none() {
+    /// Update the cache if it is missing or expired, without reading it
+    pub fn ensure_cache(&mut self) -> anyhow::Result<()> {
+        if !self.store.has_state()? || self.cache_expired()? {
             info!("updating the cache, this will take some time");
 
-            let latest = self.fetch_latest()?;
+            self.update(|dataset, outcome, _| info!("{dataset}: {outcome}"))?;
+        }
+
+        Ok(())
+    }
 
-            self.update_cache(&latest)?;
+    /// Formula or cask the given name refers to, see [resolve::resolve]
+    pub fn resolve(
+        &self,
+        name: &str,
+        kind: Option<resolve::Kind>,
+    ) -> anyhow::Result<Option<resolve::Resolved>> {
+        let tap = |kind: resolve::Kind, name: &str| {
+            let tap = match kind {
+                resolve::Kind::Formula => self.store.get_formula(name)?.map(|f| f.base.tap),
+                resolve::Kind::Cask => self.store.get_cask(name)?.map(|c| c.base.tap),
+            };
 
-            Ok(latest)
-        } else {
-            Ok(cache.unwrap())
+            Ok::<_, anyhow::Error>(tap)
+        };
+
+        let other = |kind: resolve::Kind, name: &str| match kind {
+            resolve::Kind::Formula => self.store.get_formula_name(name),
+            resolve::Kind::Cask => self.store.get_cask_name(name),
+        };
+
+        resolve::resolve(name, kind, tap, other)
+    }
+
+    /// Formula with the given name together with its installed keg
+    pub fn formula(&self, name: &str) -> anyhow::Result<Option<FormulaKeg>> {
+        let Some(formula) = self.store.get_formula(name)? else {
+            return Ok(None);
+        };
+
+        let mut installed = self.brew.installed_formula(&formula)?;
+
+        if let Some(installed) = &mut installed {
+            installed.pinned = self.store.get_pins()?.formulae.contains_key(name);
         }
+
+        Ok(Some((formula, installed)))
+    }
+
+    /// Cask with the given token together with its installed versions
+    pub fn cask(&self, token: &str) -> anyhow::Result<Option<CaskKeg>> {
+        let Some(cask) = self.store.get_cask(token)? else {
+            return Ok(None);
+        };
+
+        let mut installed = self.brew.installed_cask(&cask)?;
+
+        if let Some(installed) = &mut installed {
+            installed.pinned = self.store.get_pins()?.casks.contains_key(token);
+        }
+
+        Ok(Some((cask, installed)))
+    }
+
+    pub fn contains_formula(&self, name: &str) -> anyhow::Result<bool> {
+        self.store.contains_formula(name)
+    }
+
+    pub fn contains_cask(&self, token: &str) -> anyhow::Result<bool> {
+        self.store.contains_cask(token)
+    }
+
+    /// Every formula, without the casks and the installed kegs
+    pub fn formulae(&self) -> anyhow::Result<models::formula::Store> {
+        self.store.get_formulae()
     }
 
     pub fn cache(&self) -> anyhow::Result<Option<State>> {
@@ -65,7 +390,32 @@ impl Engine {
             return Ok(None);
         };
 
-        let installed = self.brew.installed(&all)?;
+        Ok(Some(self.state(all)?))
+    }
+
+    /// The given formulae and casks with their installed kegs and pins
+    fn state(
+        &self,
+        all: models::State<models::formula::Store, models::cask::Store>,
+    ) -> anyhow::Result<State> {
+        let (mut installed, report) = self.brew.installed(&all)?;
+
+        if !report.is_empty() {
+            warn!(
+                "skipped {} broken kegs, run `brewer doctor` for details",
+                report.len()
+            );
+        }
+
+        let pins = self.store.get_pins()?;
+
+        for (name, formula) in installed.formulae.iter_mut() {
+            formula.pinned = pins.formulae.contains_key(name);
+        }
+
+        for (token, cask) in installed.casks.iter_mut() {
+            cask.pinned = pins.casks.contains_key(token);
+        }
 
         let state = State {
             formulae: models::formula::State {
@@ -78,7 +428,46 @@ impl Engine {
             },
         };
 
-        Ok(Some(state))
+        Ok(state)
+    }
+
+    /// Run the health checks of the Homebrew prefix.
+    /// Checks which need formulae and casks are skipped if there is no cache
+    pub fn doctor(&self) -> anyhow::Result<Vec<doctor::Finding>> {
+        let prefix = &self.brew.prefix;
+
+        let mut findings = Vec::new();
+
+        findings.extend(doctor::dangling_symlinks(prefix));
+        findings.extend(doctor::unlinked_kegs(prefix));
+
+        if let Some(all) = self.store.get_state()? {
+            let (installed, report) = self.brew.installed(&all)?;
+
+            findings.extend(doctor::unknown_casks(prefix, &all.casks));
+            findings.extend(doctor::deprecated_formulae(&installed.formulae));
+            findings.extend(doctor::broken_kegs(&report));
+        }
+
+        findings.extend(doctor::stale_cache(self.cache_age()?));
+        findings.extend(doctor::unwritable_directories(prefix));
+
+        Ok(findings)
+    }
+
+    /// Time since the last update, None if the cache was never updated
+    pub fn cache_age(&self) -> anyhow::Result<Option<Duration>> {
+        let Some(last_update) = self.store.last_update()? else {
+            return Ok(None);
+        };
+
+        let age = Utc::now().naive_utc() - last_update;
+
+        Ok(Some(age.to_std().unwrap_or_default()))
+    }
+
+    pub fn cache_info(&self) -> anyhow::Result<store::Info> {
+        self.store.info()
     }
 
     pub fn cache_expired(&self) -> anyhow::Result<bool> {
@@ -98,19 +487,283 @@ impl Engine {
         }
     }
 
-    pub fn update_cache(&mut self, state: &State) -> anyhow::Result<()> {
-        self.store.set_state(store::State {
-            formulae: state.formulae.all.clone(),
-            casks: state.casks.all.clone(),
-        })?;
+    /// Fetch the datasets concurrently and rebuild the parts of the cache whose dataset changed.
+    /// Progress is called as soon as a dataset is fetched
+    pub fn update(
+        &mut self,
+        mut progress: impl FnMut(Dataset, &Outcome, Duration),
+    ) -> anyhow::Result<Vec<(Dataset, Outcome)>> {
+        let executables = self.brew.executables();
+        let analytics = self.brew.analytics();
 
-        Ok(())
+        let cached_definitions = self.store.get_validators(Dataset::Definitions)?;
+        let cached_executables = self.store.get_validators(Dataset::Executables)?;
+        let cached_analytics = self.store.get_validators(Dataset::Analytics)?;
+
+        let mut report = Vec::new();
+
+        for dataset in Dataset::ALL {
+            if self.is_enabled(dataset) {
+                continue;
+            }
+
+            // formulae keep the data of a disabled dataset until it is dropped
+            let outcome = match self.store.has_dataset(dataset)? {
+                true => Outcome::Dropped,
+                false => Outcome::Disabled,
+            };
+
+            progress(dataset, &outcome, Duration::ZERO);
+            report.push((dataset, outcome));
+        }
+
+        let brew = &self.brew;
+
+        let (definitions, executables_data, analytics_data) = thread::scope(|s| {
+            let (sender, receiver) = mpsc::channel();
+
+            let definitions = spawn_fetch(s, Dataset::Definitions, sender.clone(), || {
+                brew.eval_all_since(cached_definitions.as_ref())
+            });
+
+            let executables_data = self.is_enabled(Dataset::Executables).then(|| {
+                spawn_fetch(s, Dataset::Executables, sender.clone(), || {
+                    executables.fetch(cached_executables.as_ref())
+                })
+            });
+
+            let analytics_data = self.is_enabled(Dataset::Analytics).then(|| {
+                spawn_fetch(s, Dataset::Analytics, sender.clone(), || {
+                    analytics.fetch(cached_analytics.as_ref())
+                })
+            });
+
+            drop(sender);
+
+            for (dataset, outcome, elapsed) in receiver {
+                // failures of the required datasets are returned as errors
+                if matches!(outcome, Outcome::Failed(_)) && !dataset.is_optional() {
+                    continue;
+                }
+
+                progress(dataset, &outcome, elapsed);
+                report.push((dataset, outcome));
+            }
+
+            (
+                join(definitions),
+                executables_data.map(join),
+                analytics_data.map(join),
+            )
+        });
+
+        let definitions = definitions?;
+
+        let mut changes = store::Changes::default();
+
+        changes
+            .validators
+            .push((Dataset::Definitions, definitions.validators().clone()));
+
+        for (dataset, outcome) in &report {
+            if *outcome == Outcome::Dropped {
+                changes.removed.push(*dataset);
+            }
+        }
+
+        let rebuild = report.iter().any(|(_, o)| o.is_change());
+
+        let executables_data = self.enrichment(
+            Dataset::Executables,
+            executables_data,
+            rebuild,
+            &mut changes,
+        )?;
+        let analytics_data =
+            self.enrichment(Dataset::Analytics, analytics_data, rebuild, &mut changes)?;
+
+        if rebuild {
+            let (formulae, casks) = match definitions {
+                Fetched::Updated(all, _) => (all.formulae, all.casks),
+                Fetched::Unchanged(_) => (
+                    self.store
+                        .get_formulae()?
+                        .into_iter()
+                        .map(|(name, formula)| (name, formula.base))
+                        .collect(),
+                    self.store
+                        .get_casks()?
+                        .into_iter()
+                        .map(|(token, cask)| (token, cask.base))
+                        .collect(),
+                ),
+            };
+
+            let mut formulae = enrich::plain(formulae);
+            let mut casks = enrich::plain_casks(casks);
+
+            if let Some(data) = &executables_data {
+                enrich::apply(&executables, &mut formulae, &mut casks, data);
+            }
+
+            if let Some(data) = &analytics_data {
+                enrich::apply(&analytics, &mut formulae, &mut casks, data);
+            }
+
+            changes.formulae = Some(formulae);
+            changes.casks = Some(casks);
+        }
+
+        self.store.update(changes)?;
+        self.import_pins()?;
+
+        Ok(report)
     }
 
-    pub fn fetch_latest(&self) -> anyhow::Result<State> {
-        let state = self.brew.state()?;
+    /// Data of an optional dataset to enrich the formulae with, None if it is disabled.
+    /// The cached copy is read only if the formulae are rebuilt
+    fn enrichment<T: Serialize + DeserializeOwned>(
+        &self,
+        dataset: Dataset,
+        fetched: Option<brewer_core::Result<Fetched<T>>>,
+        rebuild: bool,
+        changes: &mut store::Changes,
+    ) -> anyhow::Result<Option<T>> {
+        let Some(fetched) = fetched else {
+            return Ok(None);
+        };
 
-        Ok(state)
+        let cached = || match rebuild {
+            true => self.store.get_dataset(dataset),
+            false => Ok(None),
+        };
+
+        match fetched {
+            Ok(fetched) => {
+                let status = store::Status {
+                    fetched: Some(Utc::now().naive_utc()),
+                    error: None,
+                };
+
+                changes.statuses.push((dataset, status));
+                changes
+                    .validators
+                    .push((dataset, fetched.validators().clone()));
+
+                match fetched {
+                    Fetched::Updated(data, _) => {
+                        changes.put_dataset(dataset, &data)?;
+
+                        Ok(Some(data))
+                    }
+                    Fetched::Unchanged(_) => cached(),
+                }
+            }
+            Err(e) => {
+                warn!("{dataset} are unavailable, keeping the cached copy: {e}");
+
+                let status = store::Status {
+                    fetched: self.store.get_status(dataset)?.and_then(|s| s.fetched),
+                    error: Some(e.to_string()),
+                };
+
+                changes.statuses.push((dataset, status));
+
+                cached()
+            }
+        }
     }
+
+    fn is_enabled(&self, dataset: Dataset) -> bool {
+        let enrichments = self.brew.enrichments;
+
+        match dataset {
+            Dataset::Definitions => true,
+            Dataset::Executables => enrichments.executables,
+            Dataset::Analytics => enrichments.analytics,
+        }
+    }
+
+    /// Whether the formulae carry current data of the optional dataset
+    pub fn availability(&self, dataset: Dataset) -> anyhow::Result<Availability> {
+        if !self.is_enabled(dataset) {
+            return Ok(Availability::Disabled);
+        }
+
+        let availability = match self.store.get_status(dataset)? {
+            // the cache was written before the statuses were recorded
+            None => Availability::Available,
+            Some(store::Status { error: None, .. }) => Availability::Available,
+            Some(store::Status {
+                fetched: Some(fetched),
+                error: Some(error),
+            }) => Availability::Stale { fetched, error },
+            Some(store::Status {
+                fetched: None,
+                error,
+            }) => Availability::Unavailable(error),
+        };
+
+        Ok(availability)
+    }
+
+    /// Formulae providing the given executable, sorted by name
+    pub fn which(&mut self, executable: &str) -> anyhow::Result<Vec<String>> {
+        self.ensure_executables_index()?;
+
+        Ok(self.store.get_executable(executable)?.unwrap_or_default())
+    }
+
+    /// All executables with the formulae providing them
+    pub fn executables(&mut self) -> anyhow::Result<Vec<store::Executable>> {
+        self.ensure_executables_index()?;
+
+        Ok(self.store.get_executables()?.unwrap_or_default())
+    }
+
+    fn ensure_executables_index(&mut self) -> anyhow::Result<()> {
+        if self.store.has_executables()? || !self.store.has_state()? {
+            return Ok(());
+        }
+
+        info!("building the executables index");
+
+        let formulae = self.store.get_formulae()?;
+
+        self.store.set_executables(&formulae)
+    }
+}
+
+/// Fetch the dataset on its own thread, sending the outcome once it is done
+fn spawn_fetch<'scope, T: Send + 'scope>(
+    scope: &'scope thread::Scope<'scope, '_>,
+    dataset: Dataset,
+    sender: mpsc::Sender<(Dataset, Outcome, Duration)>,
+    fetch: impl FnOnce() -> brewer_core::Result<Fetched<T>> + Send + 'scope,
+) -> thread::ScopedJoinHandle<'scope, brewer_core::Result<Fetched<T>>> {
+    scope.spawn(move || {
+        let start = Instant::now();
+        let result = fetch();
+        let elapsed = start.elapsed();
+
+        debug!("fetched {dataset} in {elapsed:?}");
+
+        // the receiver lives until every fetch is done
+        let _ = sender.send((dataset, Outcome::of(&result), elapsed));
+
+        result
+    })
 }
 
+fn join<T>(handle: thread::ScopedJoinHandle<T>) -> T {
+    handle
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+fn pins_of(pins: &mut Pins, kind: resolve::Kind) -> &mut pin::Store {
+    match kind {
+        resolve::Kind::Formula => &mut pins.formulae,
+        resolve::Kind::Cask => &mut pins.casks,
+    }
+}
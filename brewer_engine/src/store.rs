@@ -1,27 +1,175 @@
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 use chrono::{NaiveDateTime, Utc};
 use jammdb::Tx;
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 
-use brewer_core::models;
+use brewer_core::fetch::Validators;
+use brewer_core::{models, resolve};
 
+/// Cache of the formulae and casks.
+///
+/// Every formula and cask is stored under its own key so that point lookups
+/// do not read the whole cache. The keys of the `formulae` and `casks` buckets
+/// double as the name index, `formula_names` and `cask_names` map the other
+/// names of the formulae and casks to the canonical ones.
+///
+/// Besides the cache the store holds the pins, which survive a rebuild.
 #[derive(Clone)]
 pub struct Store {
     db: jammdb::DB,
+    path: PathBuf,
+}
+
+#[derive(Serialize)]
+pub struct Info {
+    pub path: PathBuf,
+
+    /// Size of the database file in bytes
+    pub size: u64,
+
+    /// None if the cache was written before the schema was recorded
+    pub schema: Option<u32>,
+    pub version: Option<String>,
+
+    pub last_update: Option<NaiveDateTime>,
+
+    pub formulae: usize,
+    pub casks: usize,
+    pub aliases: usize,
+    pub executables: usize,
 }
 
 pub type State = models::State<models::formula::Store, models::cask::Store>;
 
+/// Executable name and the formulae providing it
+pub type Executable = (String, Vec<String>);
+
+/// Data the records are built from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dataset {
+    /// Formulae and casks definitions
+    Definitions,
+    Executables,
+    Analytics,
+}
+
+/// Parts of the cache to replace, None keeps the cached part
+#[derive(Default)]
+pub struct Changes {
+    pub formulae: Option<models::formula::Store>,
+    pub casks: Option<models::cask::Store>,
+
+    /// Serialized data of the changed optional datasets
+    datasets: Vec<(Dataset, Vec<u8>)>,
+
+    /// Optional datasets to drop together with their validators
+    pub removed: Vec<Dataset>,
+
+    /// Validators of the fetched datasets, changed or not
+    pub validators: Vec<(Dataset, Validators)>,
+
+    pub statuses: Vec<(Dataset, Status)>,
+}
+
+/// Outcome of the last fetches of an optional dataset
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Status {
+    /// Last time the dataset was fetched, None if it never was
+    pub fetched: Option<NaiveDateTime>,
+
+    /// Why the last fetch failed, None if it succeeded
+    pub error: Option<String>,
+}
+
+impl Changes {
+    pub fn put_dataset<T: Serialize>(&mut self, dataset: Dataset, data: &T) -> anyhow::Result<()> {
+        self.datasets.push((dataset, rmp_serde::to_vec(data)?));
+
+        Ok(())
+    }
+}
+
+impl Dataset {
+    pub const ALL: [Dataset; 3] = [
+        Dataset::Definitions,
+        Dataset::Executables,
+        Dataset::Analytics,
+    ];
+
+    /// Whether the cache can be built without the dataset
+    pub fn is_optional(&self) -> bool {
+        matches!(self, Dataset::Executables | Dataset::Analytics)
+    }
+
+    fn key(&self) -> &'static str {
+        match self {
+            Dataset::Definitions => "definitions",
+            Dataset::Executables => "executables",
+            Dataset::Analytics => "analytics",
+        }
+    }
+}
+
+impl std::fmt::Display for Dataset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Dataset::Definitions => f.write_str("formulae and casks"),
+            _ => f.write_str(self.key()),
+        }
+    }
+}
+
 impl Store {
     const UPDATE_BUCKET: &'static str = "update";
+    const FORMULAE_BUCKET: &'static str = "formulae";
+    const CASKS_BUCKET: &'static str = "casks";
+    const FORMULA_NAMES_BUCKET: &'static str = "formula_names";
+    const CASK_NAMES_BUCKET: &'static str = "cask_names";
+    const EXECUTABLES_BUCKET: &'static str = "executables";
+
+    /// Validators of the datasets, by dataset
+    const VALIDATORS_BUCKET: &'static str = "validators";
+
+    /// Executables and analytics as fetched, to enrich the formulae when only the definitions change
+    const DATASETS_BUCKET: &'static str = "datasets";
+
+    /// Outcome of the last fetches of the optional datasets, by dataset
+    const STATUSES_BUCKET: &'static str = "statuses";
+
+    /// Single value layout used before the per keg buckets
     const STATE_BUCKET: &'static str = "state";
 
+    /// Aliases of the formulae used before `formula_names`
+    const ALIASES_BUCKET: &'static str = "aliases";
+
+    /// Pinned formulae and casks, by full name. Not part of the cache, kept on rebuilds
+    const FORMULA_PINS_BUCKET: &'static str = "formula_pins";
+    const CASK_PINS_BUCKET: &'static str = "cask_pins";
+
     const STATE_KEY: &'static str = "state";
+    const SCHEMA_KEY: &'static str = "schema";
+    const VERSION_KEY: &'static str = "version";
+
+    /// Version of the records layout, bump it whenever a stored model changes.
+    /// Caches of any other schema are rebuilt, the records are never probed
+    pub const SCHEMA_VERSION: u32 = 5;
+
+    /// Version of brewer which wrote the cache
+    pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
     pub fn open(path: &Path) -> anyhow::Result<Store> {
-        Ok(Store {
-            db: jammdb::DB::open(path)?
-        })
+        let mut store = Store {
+            db: jammdb::DB::open(path)?,
+            path: path.to_path_buf()
+        };
+
+        store.migrate()?;
+
+        Ok(store)
     }
 
     pub fn last_update(&self) -> anyhow::Result<Option<NaiveDateTime>> {
@@ -51,40 +199,572 @@ impl Store {
 
         bucket.put(Self::STATE_KEY, now_bytes)?;
 
+        Self::put_versions(&tx)?;
+
         tx.commit()?;
 
         Ok(())
     }
 
+    /// Every formula and cask, None if the cache was never updated
     pub fn get_state(&self) -> anyhow::Result<Option<State>> {
+        let Some(formulae) = self.get_all(Self::FORMULAE_BUCKET)? else {
+            return Ok(None);
+        };
+
+        let casks = self.get_all(Self::CASKS_BUCKET)?.unwrap_or_default();
+
+        Ok(Some(State {
+            formulae: formulae.into_iter().collect(),
+            casks: casks.into_iter().collect(),
+        }))
+    }
+
+    /// Replace the changed parts and record the validators
+    pub fn update(&mut self, changes: Changes) -> anyhow::Result<()> {
+        let tx = self.db.tx(true)?;
+
+        if let Some(formulae) = &changes.formulae {
+            Self::put_formulae(&tx, formulae)?;
+        }
+
+        if let Some(casks) = &changes.casks {
+            Self::put_casks(&tx, casks)?;
+        }
+
+        {
+            let datasets = tx.get_or_create_bucket(Self::DATASETS_BUCKET)?;
+            let validators = tx.get_or_create_bucket(Self::VALIDATORS_BUCKET)?;
+            let statuses = tx.get_or_create_bucket(Self::STATUSES_BUCKET)?;
+
+            for (dataset, data) in changes.datasets {
+                datasets.put(dataset.key(), data)?;
+            }
+
+            for (dataset, v) in &changes.validators {
+                validators.put(dataset.key(), rmp_serde::to_vec(v)?)?;
+            }
+
+            for (dataset, status) in &changes.statuses {
+                statuses.put(dataset.key(), rmp_serde::to_vec(status)?)?;
+            }
+
+            for dataset in &changes.removed {
+                for bucket in [&datasets, &validators, &statuses] {
+                    match bucket.delete(dataset.key()) {
+                        Ok(_) | Err(jammdb::Error::KeyValueMissing) => {}
+                        Err(e) => return Err(anyhow::anyhow!(e))
+                    }
+                }
+            }
+        }
+
+        Self::commit_update(tx)?;
+
+        Ok(())
+    }
+
+    /// Validators of the cached dataset, None if the dataset is not cached
+    pub fn get_validators(&self, dataset: Dataset) -> anyhow::Result<Option<Validators>> {
+        let cached = match dataset {
+            Dataset::Definitions => self.has_state()?,
+            _ => self.contains(Self::DATASETS_BUCKET, dataset.key())?,
+        };
+
+        if !cached {
+            return Ok(None);
+        }
+
+        self.get(Self::VALIDATORS_BUCKET, dataset.key())
+    }
+
+    /// Data of an optional dataset as it was last fetched
+    pub fn get_dataset<T: DeserializeOwned>(&self, dataset: Dataset) -> anyhow::Result<Option<T>> {
+        self.get(Self::DATASETS_BUCKET, dataset.key())
+    }
+
+    pub fn has_dataset(&self, dataset: Dataset) -> anyhow::Result<bool> {
+        self.contains(Self::DATASETS_BUCKET, dataset.key())
+    }
+
+    /// Outcome of the last fetches of an optional dataset,
+    /// None if the cache was written before it was recorded
+    pub fn get_status(&self, dataset: Dataset) -> anyhow::Result<Option<Status>> {
+        self.get(Self::STATUSES_BUCKET, dataset.key())
+    }
+
+    pub fn has_state(&self) -> anyhow::Result<bool> {
+        self.has_bucket(Self::FORMULAE_BUCKET)
+    }
+
+    pub fn get_formula(&self, name: &str) -> anyhow::Result<Option<models::formula::Formula>> {
+        self.get(Self::FORMULAE_BUCKET, name)
+    }
+
+    pub fn get_cask(&self, token: &str) -> anyhow::Result<Option<models::cask::Cask>> {
+        self.get(Self::CASKS_BUCKET, token)
+    }
+
+    pub fn get_formulae(&self) -> anyhow::Result<models::formula::Store> {
+        Ok(self
+            .get_all(Self::FORMULAE_BUCKET)?
+            .unwrap_or_default()
+            .into_iter()
+            .collect())
+    }
+
+    pub fn get_casks(&self) -> anyhow::Result<models::cask::Store> {
+        Ok(self
+            .get_all(Self::CASKS_BUCKET)?
+            .unwrap_or_default()
+            .into_iter()
+            .collect())
+    }
+
+    /// Formula with the given alias or old name, the name is lowercase
+    pub fn get_formula_name(&self, name: &str) -> anyhow::Result<Option<resolve::Target>> {
+        self.get(Self::FORMULA_NAMES_BUCKET, name)
+    }
+
+    /// Cask with the given display name or old token, the name is lowercase
+    pub fn get_cask_name(&self, name: &str) -> anyhow::Result<Option<resolve::Target>> {
+        self.get(Self::CASK_NAMES_BUCKET, name)
+    }
+
+    /// Whether a formula with the given name exists, without reading it
+    pub fn contains_formula(&self, name: &str) -> anyhow::Result<bool> {
+        self.contains(Self::FORMULAE_BUCKET, name)
+    }
+
+    /// Whether a cask with the given token exists, without reading it
+    pub fn contains_cask(&self, token: &str) -> anyhow::Result<bool> {
+        self.contains(Self::CASKS_BUCKET, token)
+    }
+
+    /// Formulae providing the given executable, None if nothing provides it
+    /// or the index was not built yet
+    pub fn get_executable(&self, name: &str) -> anyhow::Result<Option<Vec<String>>> {
+        self.get(Self::EXECUTABLES_BUCKET, name)
+    }
+
+    /// All executables with the formulae providing them, None if the index was not built yet
+    pub fn get_executables(&self) -> anyhow::Result<Option<Vec<Executable>>> {
+        self.get_all(Self::EXECUTABLES_BUCKET)
+    }
+
+    pub fn has_executables(&self) -> anyhow::Result<bool> {
+        self.has_bucket(Self::EXECUTABLES_BUCKET)
+    }
+
+    /// Rebuild the executables index, e.g. for a cache written before the index existed
+    pub fn set_executables(&mut self, formulae: &models::formula::Store) -> anyhow::Result<()> {
+        let tx = self.db.tx(true)?;
+
+        Self::put_executables(&tx, formulae)?;
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    pub fn get_pins(&self) -> anyhow::Result<models::pin::Pins> {
+        let formulae = self.get_all(Self::FORMULA_PINS_BUCKET)?.unwrap_or_default();
+        let casks = self.get_all(Self::CASK_PINS_BUCKET)?.unwrap_or_default();
+
+        Ok(models::pin::Pins {
+            formulae: formulae.into_iter().collect(),
+            casks: casks.into_iter().collect(),
+        })
+    }
+
+    /// Replace the pinned formulae and casks
+    pub fn set_pins(&mut self, pins: &models::pin::Pins) -> anyhow::Result<()> {
+        let tx = self.db.tx(true)?;
+
+        {
+            let formulae = Self::replace_bucket(&tx, Self::FORMULA_PINS_BUCKET)?;
+            let casks = Self::replace_bucket(&tx, Self::CASK_PINS_BUCKET)?;
+
+            for (name, pin) in &pins.formulae {
+                formulae.put(name.clone(), rmp_serde::to_vec(pin)?)?;
+            }
+
+            for (token, pin) in &pins.casks {
+                casks.put(token.clone(), rmp_serde::to_vec(pin)?)?;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Check the schema recorded with the cache. A cache of another schema, or written before
+    /// the schema was recorded, is rebuilt. The pins are kept
+    fn migrate(&mut self) -> anyhow::Result<()> {
+        let schema = self.get(Self::UPDATE_BUCKET, Self::SCHEMA_KEY)?;
+
+        match schema {
+            Some(Self::SCHEMA_VERSION) => {}
+            // there is no migration between versioned schemas yet
+            Some(schema) => {
+                info!("cache schema {schema} is not supported");
+
+                self.rebuild()?;
+            }
+            // single value or per keg records written before the schema was recorded
+            None if self.has_bucket(Self::STATE_BUCKET)? || self.has_state()? => self.rebuild()?,
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /// Drop the cached records, they are fetched again on the next run
+    fn rebuild(&mut self) -> anyhow::Result<()> {
+        warn!("the cache was written by an incompatible version of brewer and will be rebuilt");
+
+        let tx = self.db.tx(true)?;
+
+        for name in [
+            Self::UPDATE_BUCKET,
+            Self::STATE_BUCKET,
+            Self::FORMULAE_BUCKET,
+            Self::CASKS_BUCKET,
+            Self::ALIASES_BUCKET,
+            Self::FORMULA_NAMES_BUCKET,
+            Self::CASK_NAMES_BUCKET,
+            Self::EXECUTABLES_BUCKET,
+            Self::VALIDATORS_BUCKET,
+            Self::DATASETS_BUCKET,
+            Self::STATUSES_BUCKET,
+        ] {
+            match tx.delete_bucket(name) {
+                Ok(()) | Err(jammdb::Error::BucketMissing) => {}
+                Err(e) => return Err(anyhow::anyhow!(e))
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn put_versions(tx: &Tx) -> anyhow::Result<()> {
+        let bucket = tx.get_or_create_bucket(Self::UPDATE_BUCKET)?;
+
+        bucket.put(Self::SCHEMA_KEY, rmp_serde::to_vec(&Self::SCHEMA_VERSION)?)?;
+        bucket.put(Self::VERSION_KEY, rmp_serde::to_vec(Self::VERSION)?)?;
+
+        Ok(())
+    }
+
+    /// Summary of the cache contents
+    pub fn info(&self) -> anyhow::Result<Info> {
+        let tx = self.db.tx(false)?;
+
+        let count = |name: &'static str| match tx.get_bucket(name) {
+            Ok(bucket) => Ok(bucket.kv_pairs().count()),
+            Err(jammdb::Error::BucketMissing) => Ok(0),
+            Err(e) => Err(anyhow::anyhow!(e))
+        };
+
+        Ok(Info {
+            path: self.path.clone(),
+            size: std::fs::metadata(&self.path).map(|m| m.len()).unwrap_or_default(),
+            schema: self.get(Self::UPDATE_BUCKET, Self::SCHEMA_KEY)?,
+            version: self.get(Self::UPDATE_BUCKET, Self::VERSION_KEY)?,
+            last_update: self.last_update()?,
+            formulae: count(Self::FORMULAE_BUCKET)?,
+            casks: count(Self::CASKS_BUCKET)?,
+            aliases: count(Self::FORMULA_NAMES_BUCKET)?,
+            executables: count(Self::EXECUTABLES_BUCKET)?,
+        })
+    }
+
+    /// Formulae together with their other names and the executables index
+    fn put_formulae(tx: &Tx, store: &models::formula::Store) -> anyhow::Result<()> {
+        let formulae = Self::replace_bucket(tx, Self::FORMULAE_BUCKET)?;
+        let names = Self::replace_bucket(tx, Self::FORMULA_NAMES_BUCKET)?;
+
+        for (name, formula) in store {
+            formulae.put(name.clone(), rmp_serde::to_vec(formula)?)?;
+        }
+
+        for (name, target) in resolve::formula_index(store.values().map(|f| &f.base)) {
+            names.put(name, rmp_serde::to_vec(&target)?)?;
+        }
+
+        Self::put_executables(tx, store)?;
+
+        Ok(())
+    }
+
+    /// Casks together with their other names
+    fn put_casks(tx: &Tx, store: &models::cask::Store) -> anyhow::Result<()> {
+        let casks = Self::replace_bucket(tx, Self::CASKS_BUCKET)?;
+        let names = Self::replace_bucket(tx, Self::CASK_NAMES_BUCKET)?;
+
+        for (token, cask) in store {
+            casks.put(token.clone(), rmp_serde::to_vec(cask)?)?;
+        }
+
+        for (name, target) in resolve::cask_index(store.values().map(|c| &c.base)) {
+            names.put(name, rmp_serde::to_vec(&target)?)?;
+        }
+
+        Ok(())
+    }
+
+    fn put_executables(tx: &Tx, formulae: &models::formula::Store) -> anyhow::Result<()> {
+        let bucket = Self::replace_bucket(tx, Self::EXECUTABLES_BUCKET)?;
+
+        let mut index: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+
+        for (name, f) in formulae {
+            for e in &f.executables {
+                index.entry(e).or_default().push(name);
+            }
+        }
+
+        for (executable, mut names) in index {
+            names.sort_unstable();
+
+            bucket.put(executable.to_string(), rmp_serde::to_vec(&names)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Empty bucket with the given name, replacing the existing one
+    fn replace_bucket<'b, 'tx>(
+        tx: &'b Tx<'tx>,
+        name: &'static str,
+    ) -> anyhow::Result<jammdb::Bucket<'b, 'tx>> {
+        match tx.delete_bucket(name) {
+            Ok(()) | Err(jammdb::Error::BucketMissing) => {}
+            Err(e) => return Err(anyhow::anyhow!(e))
+        }
+
+        Ok(tx.create_bucket(name)?)
+    }
+
+    fn has_bucket(&self, name: &str) -> anyhow::Result<bool> {
         let tx = self.db.tx(false)?;
 
-        match tx.get_bucket(Self::STATE_BUCKET) {
+        match tx.get_bucket(name) {
+            Ok(_) => Ok(true),
+            Err(jammdb::Error::BucketMissing) => Ok(false),
+            Err(e) => Err(anyhow::anyhow!(e))
+        }
+    }
+
+    fn contains(&self, bucket: &str, key: &str) -> anyhow::Result<bool> {
+        let tx = self.db.tx(false)?;
+
+        match tx.get_bucket(bucket) {
+            Ok(bucket) => Ok(bucket.get(key).is_some()),
+            Err(jammdb::Error::BucketMissing) => Ok(false),
+            Err(e) => Err(anyhow::anyhow!(e))
+        }
+    }
+
+    fn get<T: DeserializeOwned>(&self, bucket: &str, key: &str) -> anyhow::Result<Option<T>> {
+        let tx = self.db.tx(false)?;
+
+        match tx.get_bucket(bucket) {
             Ok(bucket) => {
-                let Some(data) = bucket.get(Self::STATE_KEY) else {
+                let Some(data) = bucket.get(key) else {
                     return Ok(None);
                 };
 
-                let state: State = rmp_serde::from_slice(data.kv().value())?;
+                Ok(Some(rmp_serde::from_slice(data.kv().value())?))
+            }
+            Err(jammdb::Error::BucketMissing) => Ok(None),
+            Err(e) => Err(anyhow::anyhow!(e))
+        }
+    }
+
+    /// Every record of the bucket, None if the bucket does not exist
+    fn get_all<T: DeserializeOwned>(&self, bucket: &str) -> anyhow::Result<Option<Vec<(String, T)>>> {
+        let tx = self.db.tx(false)?;
 
-                Ok(Some(state))
+        match tx.get_bucket(bucket) {
+            Ok(bucket) => {
+                let mut records = Vec::new();
+
+                for kv in bucket.kv_pairs() {
+                    let key = String::from_utf8_lossy(kv.key()).to_string();
+                    let value: T = rmp_serde::from_slice(kv.value())?;
+
+                    records.push((key, value));
+                }
+
+                Ok(Some(records))
             }
             Err(jammdb::Error::BucketMissing) => Ok(None),
             Err(e) => Err(anyhow::anyhow!(e))
         }
     }
+}
 
-    pub fn set_state(&mut self, state: State) -> anyhow::Result<()> {
-        let tx = self.db.tx(true)?;
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::path::{Path, PathBuf};
 
-        let bucket = tx.get_or_create_bucket(Self::STATE_BUCKET)?;
+    use brewer_core::fetch::Validators;
+    use brewer_core::models::{formula, pin};
 
-        let state_bytes = rmp_serde::to_vec(&state)?;
+    use super::{Changes, Dataset, Store};
 
-        bucket.put(Self::STATE_KEY, state_bytes)?;
+    /// Path of an empty database for the given test
+    fn path(test: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("brewer-{test}-{}.db", std::process::id()));
 
-        Self::commit_update(tx)?;
+        let _ = std::fs::remove_file(&path);
 
-        Ok(())
+        path
     }
-}
\ No newline at end of file
+
+    fn formula(name: &str) -> formula::Formula {
+        formula::Formula {
+            base: formula::base::Formula {
+                name: name.to_string(),
+                tap: "homebrew/core".to_string(),
+                desc: None,
+                homepage: None,
+                caveats: None,
+                build_dependencies: Vec::new(),
+                dependencies: Vec::new(),
+                deprecated: false,
+                deprecation_reason: None,
+                disabled: false,
+                disable_reason: None,
+                aliases: HashSet::new(),
+                oldnames: HashSet::new(),
+                versions: formula::base::Versions {
+                    stable: "1.0".to_string(),
+                    head: None,
+                },
+                revision: 0,
+            },
+            executables: HashSet::new(),
+            analytics: None,
+        }
+    }
+
+    fn validators(hash: &str) -> Validators {
+        Validators {
+            hash: Some(hash.to_string()),
+            ..Validators::default()
+        }
+    }
+
+    /// Store with a cached formula and a pinned one
+    fn populated(path: &Path) -> Store {
+        let mut store = Store::open(path).unwrap();
+
+        store
+            .update(Changes {
+                formulae: Some([("wget".to_string(), formula("wget"))].into()),
+                casks: Some(Default::default()),
+                validators: vec![(Dataset::Definitions, validators("a"))],
+                ..Changes::default()
+            })
+            .unwrap();
+
+        let mut pins = pin::Pins::default();
+        pins.formulae.insert("wget".to_string(), pin::Pin { source: pin::Source::Brewer });
+        store.set_pins(&pins).unwrap();
+
+        store
+    }
+
+    fn put_raw(store: &Store, bucket: &str, key: &str, value: &impl serde::Serialize) {
+        let tx = store.db.tx(true).unwrap();
+
+        tx.get_or_create_bucket(bucket).unwrap().put(key, rmp_serde::to_vec(value).unwrap()).unwrap();
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn schema_mismatch_rebuilds_and_keeps_the_pins() {
+        let path = path("schema-mismatch");
+        let store = populated(&path);
+
+        put_raw(&store, Store::UPDATE_BUCKET, Store::SCHEMA_KEY, &(Store::SCHEMA_VERSION - 1));
+        drop(store);
+
+        let store = Store::open(&path).unwrap();
+
+        assert!(!store.has_state().unwrap());
+        assert_eq!(store.get_validators(Dataset::Definitions).unwrap(), None);
+        assert_eq!(store.last_update().unwrap(), None);
+        assert!(store.get_pins().unwrap().formulae.contains_key("wget"));
+    }
+
+    #[test]
+    fn single_value_cache_is_rebuilt() {
+        let path = path("single-value");
+        let store = Store::open(&path).unwrap();
+
+        put_raw(&store, Store::STATE_BUCKET, Store::STATE_KEY, &"formulae and casks");
+        drop(store);
+
+        let store = Store::open(&path).unwrap();
+
+        assert!(!store.has_bucket(Store::STATE_BUCKET).unwrap());
+    }
+
+    #[test]
+    fn current_schema_is_kept() {
+        let path = path("current-schema");
+        drop(populated(&path));
+
+        let store = Store::open(&path).unwrap();
+
+        assert!(store.get_formula("wget").unwrap().is_some());
+        assert_eq!(store.get_validators(Dataset::Definitions).unwrap(), Some(validators("a")));
+    }
+
+    #[test]
+    fn unchanged_definitions_keep_the_records() {
+        let path = path("unchanged-definitions");
+        let mut store = populated(&path);
+
+        store
+            .update(Changes {
+                validators: vec![(Dataset::Definitions, validators("a"))],
+                ..Changes::default()
+            })
+            .unwrap();
+
+        assert!(store.get_formula("wget").unwrap().is_some());
+        assert_eq!(store.get_validators(Dataset::Definitions).unwrap(), Some(validators("a")));
+    }
+
+    #[test]
+    fn pins_round_trip() {
+        let path = path("pins");
+        let mut store = Store::open(&path).unwrap();
+
+        assert!(store.get_pins().unwrap().formulae.is_empty());
+
+        let mut pins = pin::Pins::default();
+        pins.formulae.insert("wget".to_string(), pin::Pin { source: pin::Source::Homebrew });
+        pins.casks.insert("firefox".to_string(), pin::Pin { source: pin::Source::Brewer });
+        store.set_pins(&pins).unwrap();
+
+        let read = store.get_pins().unwrap();
+        assert_eq!(read.formulae["wget"].source, pin::Source::Homebrew);
+        assert_eq!(read.casks["firefox"].source, pin::Source::Brewer);
+
+        pins.formulae.remove("wget");
+        store.set_pins(&pins).unwrap();
+
+        let read = store.get_pins().unwrap();
+        assert!(read.formulae.is_empty());
+        assert_eq!(read.casks.len(), 1);
+    }
+}
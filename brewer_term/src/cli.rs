@@ -1,4 +1,6 @@
+use std::cmp::Reverse;
 use std::io::{BufWriter, Write};
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use clap::{Args, Parser, Subcommand};
@@ -8,9 +10,11 @@ use skim::prelude::{unbounded, SkimOptionsBuilder};
 use skim::{Skim, SkimItem, SkimItemReceiver, SkimItemSender};
 use terminal_size::{terminal_size, Width};
 
-use brewer_core::models;
-use brewer_engine::{Engine, State};
+use brewer_core::{models, resolve};
+use brewer_engine::store::Dataset;
+use brewer_engine::{Engine, Outcome, State};
 
+use crate::output::{self, Format};
 use crate::pretty;
 use crate::pretty::header;
 
@@ -22,6 +26,11 @@ pub struct Cli {
 
     #[command(flatten)]
     pub verbose: Verbosity,
+
+    /// Output format of the read commands.
+    /// Defaults to table when stdout is a terminal and plain otherwise
+    #[clap(long, global = true, value_enum)]
+    pub format: Option<Format>,
 }
 
 #[derive(Subcommand)]
@@ -49,6 +58,15 @@ pub enum Commands {
     /// Indicate if the given formula or cask exists by exit code.
     Exists(Exists),
 
+    /// Show dependencies of the given formula
+    Deps(deps::Deps),
+
+    /// Show formulae which depend on the given formula
+    Uses(deps::Uses),
+
+    /// Show installed formulae and casks which have newer versions available
+    Outdated(outdated::Outdated),
+
     /// Install the given formula or cask.
     #[clap(alias = "i")]
     Install(install::Install),
@@ -56,21 +74,80 @@ pub enum Commands {
     /// Uninstall the given formula or cask.
     #[clap(aliases = & ["r", "remove"])]
     Uninstall(uninstall::Uninstall),
+
+    /// Upgrade the given outdated formula or cask.
+    #[clap(alias = "u")]
+    Upgrade(upgrade::Upgrade),
+
+    /// Dump, check and install the formulae and casks of a Brewfile
+    Bundle(bundle::Bundle),
+
+    /// Write the installed versions to a lockfile or check the prefix against it
+    Lock(lock::Lock),
+
+    /// Hold the given installed formulae or casks at their version
+    Pin(pin::Pin),
+
+    /// Let the given pinned formulae or casks be upgraded again
+    Unpin(pin::Unpin),
+
+    /// Check the Homebrew prefix for common problems
+    Doctor(doctor::Doctor),
+
+    /// Show caveats of the installed formulae and casks
+    Caveats(caveats::Caveats),
+
+    /// List the taps of the known formulae and casks
+    Tap(tap::Tap),
+
+    /// Inspect the local cache
+    Cache(cache::Cache),
+}
+
+/// Sort key putting the most installed in the window first and the unranked last
+fn rank_key(
+    window: models::analytics::Window,
+    analytics: Option<&models::analytics::Analytics>,
+) -> Reverse<Option<u64>> {
+    Reverse(analytics.and_then(|a| a.installs(window)))
 }
 
 pub mod which {
     use std::borrow::Cow;
-    use std::collections::HashMap;
-    use std::io::{BufWriter, IsTerminal, Write};
+    use std::io::{BufWriter, Write};
+    use std::sync::Arc;
 
     use clap::Args;
     use colored::Colorize;
     use skim::{ItemPreview, PreviewContext, SkimItem};
 
     use brewer_core::models;
-    use brewer_engine::State;
+    use brewer_engine::store::Dataset;
+    use brewer_engine::{Availability, Engine};
+
+    use crate::cli::{info_formula, rank_key, select_skim};
+    use crate::output::{self, Format};
+    use crate::pretty::header;
 
-    use crate::cli::{info_formula, select_skim};
+    /// Why the executables might be incomplete, None if they are current
+    fn notice(availability: Availability) -> Option<String> {
+        match availability {
+            Availability::Available => None,
+            Availability::Disabled => Some(
+                "Executables are disabled, set enrichments.executables to locate them".to_string(),
+            ),
+            Availability::Unavailable(Some(error)) => {
+                Some(format!("Executables are unavailable: {error}"))
+            }
+            Availability::Unavailable(None) => {
+                Some("Executables are unavailable, run `brewer update`".to_string())
+            }
+            Availability::Stale { fetched, error } => Some(format!(
+                "Executables are stale, last fetched on {}: {error}",
+                fetched.format("%Y-%m-%d")
+            )),
+        }
+    }
 
     #[derive(Args)]
     pub struct Which {
@@ -79,42 +156,53 @@ pub mod which {
         /// Show all matched formulae instead of the most popular one.
         #[clap(long, short, action)]
         pub all: bool,
+
+        /// Install counts window deciding the most popular formula
+        #[clap(long, value_enum, default_value_t)]
+        pub rank_by: models::analytics::Window,
     }
 
     impl Which {
-        pub fn run(&self, state: State) -> anyhow::Result<bool> {
+        pub fn run(&self, mut engine: Engine, format: Format) -> anyhow::Result<bool> {
+            engine.ensure_cache()?;
+
+            if let Some(notice) = notice(engine.availability(Dataset::Executables)?) {
+                eprintln!("{}", header::warning!("{notice}"));
+            }
+
             let name = if let Some(name) = &self.name {
                 name.to_string()
             } else {
-                self.run_skim(&state)?
+                self.run_skim(&mut engine)?
             };
 
-            let mut formulae: Vec<_> = state
-                .formulae
-                .all
-                .into_iter()
-                .filter_map(|(_, f)| {
-                    if f.executables.contains(&name) {
-                        Some(f)
-                    } else {
-                        None
-                    }
-                })
-                .collect();
+            let mut formulae = Vec::new();
+
+            for name in engine.which(&name)? {
+                if let Some(formula) = engine.formula(&name)? {
+                    formulae.push(formula);
+                }
+            }
 
             if formulae.is_empty() {
                 return Ok(false);
             }
 
-            formulae.sort_unstable_by_key(|f| {
-                f.analytics.as_ref().map(|a| a.number).unwrap_or_default()
-            });
+            formulae.sort_by_key(|(f, _)| rank_key(self.rank_by, f.analytics.as_ref()));
 
             let mut buf = BufWriter::new(std::io::stdout());
 
-            if std::io::stdout().is_terminal() {
+            if format.is_json() {
+                let formulae: Vec<_> = formulae
+                    .iter()
+                    .take(if self.all { formulae.len() } else { 1 })
+                    .map(|(f, installed)| output::Keg::formula(f, installed.as_ref()))
+                    .collect();
+
+                output::write(&mut buf, format, &formulae)?;
+            } else if format == Format::Table {
                 if self.all {
-                    for (i, f) in formulae.iter().enumerate() {
+                    for (i, (f, _)) in formulae.iter().enumerate() {
                         info_formula(&mut buf, f, None)?;
 
                         if i != formulae.len() - 1 {
@@ -123,7 +211,7 @@ pub mod which {
                     }
                 } else {
                     // we return early if formulae is empty, so we have at least 1 element
-                    let first = formulae.first().unwrap();
+                    let (first, _) = formulae.first().unwrap();
 
                     info_formula(&mut buf, first, None)?;
 
@@ -132,7 +220,7 @@ pub mod which {
                     if !rest.is_empty() {
                         write!(buf, "Command {} is also provided by", name.purple().bold())?;
 
-                        for f in rest {
+                        for (f, _) in rest {
                             write!(buf, " {}", f.base.name.cyan().bold())?;
                         }
 
@@ -146,7 +234,7 @@ pub mod which {
                     formulae.into_iter().take(1).collect()
                 };
 
-                for f in formulae {
+                for (f, _) in formulae {
                     writeln!(buf, "{}", f.base.name)?;
                 }
             }
@@ -156,29 +244,17 @@ pub mod which {
             Ok(true)
         }
 
-        fn run_skim(&self, state: &State) -> anyhow::Result<String> {
-            let mut executables: HashMap<String, models::formula::Store> = HashMap::new();
-
-            for f in state.formulae.all.values() {
-                for e in f.executables.iter() {
-                    match executables.get_mut(e) {
-                        Some(store) => {
-                            store.insert(f.base.name.clone(), f.clone());
-                        }
-                        None => {
-                            let mut store = HashMap::new();
-
-                            store.insert(f.base.name.clone(), f.clone());
-
-                            executables.insert(e.clone(), store);
-                        }
-                    }
-                }
-            }
+        fn run_skim(&self, engine: &mut Engine) -> anyhow::Result<String> {
+            let formulae = Arc::new(engine.formulae()?);
 
-            let executables = executables
+            let executables = engine
+                .executables()?
                 .into_iter()
-                .map(|(name, provided_by)| Executable { name, provided_by });
+                .map(|(name, provided_by)| Executable {
+                    name,
+                    provided_by,
+                    formulae: Arc::clone(&formulae),
+                });
 
             let selected = select_skim(executables, "Executables", false)?;
             let selected = selected.into_iter().map(|e| e.name).take(1).collect();
@@ -190,11 +266,14 @@ pub mod which {
     #[derive(Clone)]
     struct Executable {
         pub name: String,
-        pub provided_by: models::formula::Store,
+        pub provided_by: Vec<String>,
+
+        /// Shared by all the items, only the names are stored per executable
+        pub formulae: Arc<models::formula::Store>,
     }
 
     impl SkimItem for Executable {
-        fn text(&self) -> Cow<str> {
+        fn text(&self) -> Cow<'_, str> {
             Cow::Borrowed(&self.name)
         }
 
@@ -204,10 +283,16 @@ pub mod which {
             writeln!(w, "Provided by").unwrap();
             writeln!(w).unwrap();
 
-            for (i, f) in self.provided_by.values().enumerate() {
+            let provided_by: Vec<_> = self
+                .provided_by
+                .iter()
+                .filter_map(|n| self.formulae.get(n))
+                .collect();
+
+            for (i, f) in provided_by.iter().enumerate() {
                 info_formula(&mut w, f, None).unwrap();
 
-                if i != self.provided_by.len() - 1 {
+                if i != provided_by.len() - 1 {
                     writeln!(w).unwrap();
                 }
             }
@@ -221,21 +306,48 @@ pub mod which {
 }
 
 #[derive(Args)]
-pub struct Update {}
+pub struct Update {
+    /// Build the cache from a local mirror instead, laid out as
+    /// https://formulae.brew.sh/api/ with executables.txt next to it
+    #[clap(long, value_name = "DIR")]
+    pub from: Option<PathBuf>,
+}
 
 impl Update {
     pub fn run(&self, mut engine: Engine) -> anyhow::Result<()> {
         println!("Updating the database, this will take some time");
 
-        let state = engine.fetch_latest()?;
+        let mut done = 0;
 
-        engine.update_cache(&state)?;
+        let report = engine.update(|dataset, outcome, elapsed| {
+            done += 1;
 
-        println!(
-            "Database updated, found {} formulae and {} casks",
-            state.formulae.all.len(),
-            state.casks.all.len()
-        );
+            let total = Dataset::ALL.len();
+
+            match outcome {
+                Outcome::Disabled | Outcome::Dropped => {
+                    println!("[{done}/{total}] {dataset}: {outcome}")
+                }
+                _ => println!(
+                    "[{done}/{total}] {dataset}: {outcome} ({:.1}s)",
+                    elapsed.as_secs_f32()
+                ),
+            }
+        })?;
+
+        let info = engine.cache_info()?;
+
+        if report.iter().any(|(_, o)| o.is_change()) {
+            println!(
+                "Database updated, found {} formulae and {} casks",
+                info.formulae, info.casks
+            );
+        } else {
+            println!(
+                "Database is up to date, {} formulae and {} casks",
+                info.formulae, info.casks
+            );
+        }
 
         Ok(())
     }
@@ -258,25 +370,84 @@ pub struct List {
     /// List the formulae installed as dependencies.
     #[clap(short = 'd', long, action, group = "installed")]
     pub installed_as_dependency: bool,
+
+    /// List only deprecated and disabled formulae and casks
+    #[clap(long, action)]
+    pub deprecated: bool,
+
+    /// List only formulae and casks of the given tap, e.g. homebrew/core
+    #[clap(long)]
+    pub tap: Option<String>,
+
+    /// List only pinned formulae and casks
+    #[clap(long, action)]
+    pub pinned: bool,
 }
 
 impl List {
-    pub fn run(&self, state: State) -> anyhow::Result<()> {
+    pub fn run(&self, state: State, format: Format) -> anyhow::Result<()> {
+        let mut formulae: Vec<_> = if self.casks {
+            Vec::new()
+        } else {
+            state
+                .formulae
+                .installed
+                .values()
+                .filter(|f| self.filter_formula(f))
+                .collect()
+        };
+
+        let mut casks: Vec<_> = if self.formulae {
+            Vec::new()
+        } else {
+            state
+                .casks
+                .installed
+                .values()
+                .filter(|c| !self.deprecated || !c.upstream.base.status().is_active())
+                .filter(|c| self.is_listed_tap(&c.upstream.base.tap))
+                .filter(|c| !self.pinned || c.pinned)
+                .collect()
+        };
+
+        formulae.sort_unstable_by_key(|f| f.upstream.base.full_name());
+        casks.sort_unstable_by_key(|c| c.upstream.base.full_token());
+
         let mut buf = BufWriter::new(std::io::stdout());
 
-        let max_width = terminal_size().map(|(Width(w), _)| w).unwrap_or(80);
+        match format {
+            Format::Json | Format::Ndjson => {
+                let formulae = formulae
+                    .iter()
+                    .map(|f| output::Keg::formula(&f.upstream, Some(f)));
+                let casks = casks
+                    .iter()
+                    .map(|c| output::Keg::cask(&c.upstream, Some(c)));
 
-        if self.formulae {
-            self.list_formulae(&mut buf, max_width, state.formulae.installed)?;
-            return Ok(());
-        }
+                let kegs: Vec<_> = formulae.chain(casks).collect();
 
-        if !self.casks {
-            self.list_formulae(&mut buf, max_width, state.formulae.installed)?;
-        }
+                output::write(&mut buf, format, &kegs)?;
+            }
+            Format::Plain => {
+                for f in formulae {
+                    writeln!(buf, "{}", f.upstream.base.full_name())?;
+                }
+
+                for c in casks {
+                    writeln!(buf, "{}", c.upstream.base.full_token())?;
+                }
+            }
+            Format::Table => {
+                let max_width = terminal_size().map(|(Width(w), _)| w).unwrap_or(80);
+
+                if !self.casks {
+                    self.list_formulae(&mut buf, max_width, formulae)?;
+                }
 
-        if !self.formulae {
-            self.list_casks(&mut buf, max_width, state.casks.installed)?;
+                if !self.formulae {
+                    self.list_casks(&mut buf, max_width, casks)?;
+                }
+            }
         }
 
         buf.flush()?;
@@ -284,40 +455,54 @@ impl List {
         Ok(())
     }
 
+    fn filter_formula(&self, formula: &models::formula::installed::Formula) -> bool {
+        if self.deprecated && formula.upstream.base.status().is_active() {
+            return false;
+        }
+
+        if !self.is_listed_tap(&formula.upstream.base.tap) {
+            return false;
+        }
+
+        if self.pinned && !formula.pinned {
+            return false;
+        }
+
+        if self.installed_as_dependency {
+            return formula.receipt.installed_as_dependency;
+        }
+
+        if self.installed_on_request {
+            return formula.receipt.installed_on_request;
+        }
+
+        true
+    }
+
+    fn is_listed_tap(&self, tap: &str) -> bool {
+        self.tap.as_deref().is_none_or(|t| t == tap)
+    }
+
     fn list_formulae(
         &self,
         w: &mut impl Write,
         max_width: u16,
-        formulae: models::formula::installed::Store,
+        formulae: Vec<&models::formula::installed::Formula>,
     ) -> anyhow::Result<()> {
         writeln!(w, "{}", header::primary!("Formulae"))?;
-        let mut installed: Vec<_> = formulae
-            .into_values()
-            .filter_map(|f| {
-                let name = f.upstream.base.name;
-
-                if self.installed_as_dependency {
-                    return if f.receipt.installed_as_dependency {
-                        Some(name)
-                    } else {
-                        None
-                    };
-                }
-
-                if self.installed_on_request {
-                    return if f.receipt.installed_on_request {
-                        Some(name)
-                    } else {
-                        None
-                    };
-                }
 
-                Some(name)
+        let installed: Vec<_> = formulae
+            .into_iter()
+            .map(|f| {
+                table_name(
+                    &f.upstream.base.full_name(),
+                    false,
+                    f.pinned,
+                    f.upstream.base.status(),
+                )
             })
             .collect();
 
-        installed.sort_unstable();
-
         let table = pretty::table(&installed, max_width);
 
         table.print(w)?;
@@ -329,13 +514,21 @@ impl List {
         &self,
         w: &mut impl Write,
         max_width: u16,
-        casks: models::cask::installed::Store,
+        casks: Vec<&models::cask::installed::Cask>,
     ) -> anyhow::Result<()> {
         writeln!(w, "{}", header::primary!("Casks"))?;
 
-        let mut installed: Vec<_> = casks.into_values().map(|v| v.upstream.base.token).collect();
-
-        installed.sort_unstable();
+        let installed: Vec<_> = casks
+            .into_iter()
+            .map(|c| {
+                table_name(
+                    &c.upstream.base.full_token(),
+                    false,
+                    c.pinned,
+                    c.upstream.base.status(),
+                )
+            })
+            .collect();
 
         let table = pretty::table(&installed, max_width);
 
@@ -363,44 +556,40 @@ pub struct Info {
 }
 
 impl Info {
-    pub fn run(&self, state: State) -> anyhow::Result<bool> {
-        if self.cask {
-            let Some(cask) = state.casks.all.get(&self.name) else {
-                return Ok(false);
-            };
+    pub fn run(&self, mut engine: Engine, format: Format) -> anyhow::Result<bool> {
+        engine.ensure_pins()?;
 
-            self.handle_cask(cask, state.casks.installed.get(&self.name))?;
+        let kind = kind(self.formula, self.cask);
 
-            return Ok(true);
-        }
-
-        if self.formula {
-            let Some(formula) = state.formulae.all.get(&self.name) else {
-                return Ok(false);
-            };
+        let Some(resolved) = notice_resolved(&self.name, engine.resolve(&self.name, kind)?) else {
+            return Ok(false);
+        };
 
-            self.handle_formula(formula, state.formulae.installed.get(&self.name))?;
+        match resolved.kind {
+            resolve::Kind::Formula => {
+                if let Some((formula, installed)) = engine.formula(&resolved.name)? {
+                    self.handle_formula(&formula, installed.as_ref(), format)?;
 
-            return Ok(true);
-        }
+                    return Ok(true);
+                }
+            }
+            resolve::Kind::Cask => {
+                if let Some((cask, installed)) = engine.cask(&resolved.name)? {
+                    self.handle_cask(&cask, installed.as_ref(), format)?;
 
-        match state.formulae.all.get(&self.name) {
-            Some(formula) => {
-                self.handle_formula(formula, state.formulae.installed.get(&self.name))?
+                    return Ok(true);
+                }
             }
-            None => match state.casks.all.get(&self.name) {
-                Some(cask) => self.handle_cask(cask, state.casks.installed.get(&self.name))?,
-                None => return Ok(false),
-            },
-        };
+        }
 
-        Ok(true)
+        Ok(false)
     }
 
     pub fn handle_formula(
         &self,
         formula: &models::formula::Formula,
         installed: Option<&models::formula::installed::Formula>,
+        format: Format,
     ) -> anyhow::Result<()> {
         if self.open_homepage {
             if let Some(homepage) = &formula.base.homepage {
@@ -411,7 +600,11 @@ impl Info {
 
         let mut buf = BufWriter::new(std::io::stdout());
 
-        info_formula(&mut buf, formula, installed)?;
+        if format.is_json() {
+            output::write_one(&mut buf, format, &output::Keg::formula(formula, installed))?;
+        } else {
+            info_formula(&mut buf, formula, installed)?;
+        }
 
         buf.flush()?;
 
@@ -422,6 +615,7 @@ impl Info {
         &self,
         cask: &models::cask::Cask,
         installed: Option<&models::cask::installed::Cask>,
+        format: Format,
     ) -> anyhow::Result<()> {
         if self.open_homepage {
             if let Some(homepage) = &cask.base.homepage {
@@ -432,7 +626,11 @@ impl Info {
 
         let mut buf = BufWriter::new(std::io::stdout());
 
-        info_cask(&mut buf, cask, installed)?;
+        if format.is_json() {
+            output::write_one(&mut buf, format, &output::Keg::cask(cask, installed))?;
+        } else {
+            info_cask(&mut buf, cask, installed)?;
+        }
 
         buf.flush()?;
 
@@ -449,21 +647,29 @@ fn info_formula(
         buf,
         "{}",
         header::primary!(
-            "{} {} (Cask)",
+            "{} {} (Formula)",
             &formula.base.name,
             formula.base.versions.stable
         )
     )?;
     writeln!(buf, "From {}", formula.base.tap.yellow())?;
 
+    write_status(&mut buf, "formula", formula.base.status())?;
+
     if let Some(installed) = installed {
         writeln!(buf)?;
-        writeln!(
+        write!(
             buf,
             "Installed {} {}",
             installed.receipt.source.version(),
             pretty::bool(true)
         )?;
+
+        if installed.pinned {
+            write!(buf, " {}", pretty::pinned())?;
+        }
+
+        writeln!(buf)?;
     }
 
     if let Some(homepage) = &formula.base.homepage {
@@ -497,6 +703,17 @@ fn info_formula(
         writeln!(buf)?;
     }
 
+    if let Some(analytics) = &formula.analytics {
+        writeln!(buf)?;
+        write_installs(&mut buf, "Installs", &analytics.install)?;
+        write_installs(&mut buf, "On request", &analytics.install_on_request)?;
+    }
+
+    if let Some(caveats) = &formula.base.caveats {
+        writeln!(buf)?;
+        write_caveats(&mut buf, caveats)?;
+    }
+
     Ok(())
 }
 
@@ -508,16 +725,25 @@ fn info_cask(
     writeln!(
         buf,
         "{}",
-        header::primary!("{} {} (Formula)", &cask.base.token, cask.base.version)
+        header::primary!("{} {} (Cask)", &cask.base.token, cask.base.version)
     )?;
     writeln!(buf, "From {}", cask.base.tap.yellow())?;
+
+    write_status(&mut *buf, "cask", cask.base.status())?;
+
     writeln!(buf)?;
 
     if let Some(installed) = installed {
         let versions: Vec<_> = installed.versions.iter().cloned().collect();
         let versions = versions.join(", ");
 
-        writeln!(buf, "Installed {versions} {}", pretty::bool(true))?;
+        write!(buf, "Installed {versions} {}", pretty::bool(true))?;
+
+        if installed.pinned {
+            write!(buf, " {}", pretty::pinned())?;
+        }
+
+        writeln!(buf)?;
         writeln!(buf)?;
     }
 
@@ -534,12 +760,117 @@ fn info_cask(
 
     writeln!(buf, "{}", desc.italic())?;
 
+    // casks are always installed on request, the counts are the same
+    if let Some(analytics) = &cask.analytics {
+        writeln!(buf)?;
+        write_installs(&mut *buf, "Installs", &analytics.install)?;
+    }
+
+    if let Some(caveats) = &cask.base.caveats {
+        writeln!(buf)?;
+        write_caveats(&mut *buf, caveats)?;
+    }
+
+    Ok(())
+}
+
+/// Install counts of every window, e.g. `Installs 1,234 (30d) 5,678 (90d) 9,012 (365d)`
+fn write_installs(
+    mut buf: impl Write,
+    label: &str,
+    windows: &models::analytics::Windows,
+) -> anyhow::Result<()> {
+    write!(buf, "{label}")?;
+
+    for window in models::analytics::Window::ALL {
+        match windows.get(window) {
+            Some(count) => write!(buf, " {} ({window})", pretty::count(count.count).bold())?,
+            None => write!(buf, " - ({window})")?,
+        }
+    }
+
+    writeln!(buf)?;
+
+    Ok(())
+}
+
+fn write_caveats(mut buf: impl Write, caveats: &str) -> anyhow::Result<()> {
+    writeln!(buf, "{}", header::primary!("Caveats"))?;
+    writeln!(buf, "{}", caveats.trim_end())?;
+
+    Ok(())
+}
+
+fn write_status(
+    mut buf: impl Write,
+    kind: &str,
+    status: models::keg::Status,
+) -> anyhow::Result<()> {
+    let Some(label) = pretty::status(status) else {
+        return Ok(());
+    };
+
+    writeln!(buf)?;
+
+    match status.reason() {
+        Some(reason) => writeln!(buf, "This {kind} is {label}: {reason}")?,
+        None => writeln!(buf, "This {kind} is {label}")?,
+    }
+
     Ok(())
 }
 
+/// Kind of keg the names are restricted to by the `--formula` and `--cask` flags
+fn kind(formula: bool, cask: bool) -> Option<resolve::Kind> {
+    match (formula, cask) {
+        (true, _) => Some(resolve::Kind::Formula),
+        (_, true) => Some(resolve::Kind::Cask),
+        _ => None,
+    }
+}
+
+/// Tell which keg the given name was resolved to, if it is not the canonical name
+fn notice_resolved(given: &str, resolved: Option<resolve::Resolved>) -> Option<resolve::Resolved> {
+    if let Some(notice) = resolved.as_ref().and_then(|r| r.notice(given)) {
+        eprintln!("{}", header::primary!("{notice}"));
+    }
+
+    resolved
+}
+
+/// Canonical name of the given formula, the name as given if it does not resolve
+fn formula_name(state: &State, name: &str) -> String {
+    let resolver = resolve::Resolver::new(&state.formulae.all, &state.casks.all);
+    let resolved = resolver.resolve(name, Some(resolve::Kind::Formula));
+
+    match notice_resolved(name, resolved) {
+        Some(resolved) => resolved.name,
+        None => name.to_string(),
+    }
+}
+
+/// Name followed by the installed, pinned and deprecated markers, as shown in the tables
+fn table_name(name: &str, installed: bool, pinned: bool, status: models::keg::Status) -> String {
+    let mut name = name.to_string();
+
+    if installed {
+        name = format!("{name} {}", pretty::bool(true));
+    }
+
+    if pinned {
+        name = format!("{name} {}", pretty::pinned());
+    }
+
+    if let Some(label) = pretty::status(status) {
+        name = format!("{name} {label}");
+    }
+
+    name
+}
+
 pub mod search {
     use std::borrow::Cow;
-    use std::io::{BufWriter, IsTerminal, Write};
+    use std::io::{BufWriter, Write};
 
     use clap::Args;
     use nucleo_matcher::pattern::{Atom, AtomKind, CaseMatching, Normalization};
@@ -549,18 +880,32 @@ pub mod search {
     use brewer_core::models;
     use brewer_engine::State;
 
-    use crate::cli::{info_cask, info_formula, select_skim};
+    use crate::cli::{info_cask, info_formula, rank_key, select_skim, table_name};
+    use crate::output::{self, Format};
     use crate::pretty;
     use crate::pretty::header;
 
     #[derive(Args)]
     pub struct Search {
         pub name: Option<String>,
+
+        /// Order the matches by install counts in the given window instead of by name
+        #[clap(long, value_enum)]
+        pub rank_by: Option<models::analytics::Window>,
+
+        /// Search only formulae and casks of the given tap, e.g. homebrew/core
+        #[clap(long)]
+        pub tap: Option<String>,
     }
 
     impl Search {
-        pub fn run(&self, state: State) -> anyhow::Result<bool> {
-            let kegs = match &self.name {
+        pub fn run(&self, mut state: State, format: Format) -> anyhow::Result<bool> {
+            if let Some(tap) = &self.tap {
+                state.formulae.all.retain(|_, f| &f.base.tap == tap);
+                state.casks.all.retain(|_, c| &c.base.tap == tap);
+            }
+
+            let mut kegs = match &self.name {
                 Some(name) => {
                     let mut matcher = nucleo_matcher::Matcher::new(nucleo_matcher::Config::DEFAULT);
 
@@ -576,7 +921,7 @@ pub mod search {
                     let mut formulae: Vec<_> = formulae
                         .into_iter()
                         .map(|(formula, _)| {
-                            let installed = state.formulae.installed.get(&formula.base.name);
+                            let installed = state.formulae.installed.get(&formula.base.full_name());
 
                             Keg::Formula(formula, Box::new(installed.cloned()))
                         })
@@ -586,9 +931,9 @@ pub mod search {
                     let mut casks: Vec<_> = casks
                         .into_iter()
                         .map(|(cask, _)| {
-                            let installed = state.casks.installed.get(&cask.base.token);
+                            let installed = state.casks.installed.get(&cask.base.full_token());
 
-                            Keg::Cask(cask, installed.cloned())
+                            Keg::Cask(cask, Box::new(installed.cloned()))
                         })
                         .collect();
 
@@ -603,11 +948,37 @@ pub mod search {
                 return Ok(false);
             }
 
-            if !std::io::stdout().is_terminal() {
+            if let Some(rank_by) = self.rank_by {
+                kegs.sort_by_key(|keg| rank_key(rank_by, keg.analytics()));
+            }
+
+            if format.is_json() {
+                let records: Vec<_> = kegs
+                    .iter()
+                    .map(|keg| match keg {
+                        Keg::Formula(formula, installed) => {
+                            output::Keg::formula(formula, installed.as_ref().as_ref())
+                        }
+                        Keg::Cask(cask, installed) => {
+                            output::Keg::cask(cask, installed.as_ref().as_ref())
+                        }
+                    })
+                    .collect();
+
+                let mut buf = BufWriter::new(std::io::stdout());
+
+                output::write(&mut buf, format, &records)?;
+
+                buf.flush()?;
+
+                return Ok(true);
+            }
+
+            if format == Format::Plain {
                 for keg in kegs {
                     match keg {
-                        Keg::Formula(formula, _) => println!("{}", formula.base.name),
-                        Keg::Cask(cask, _) => println!("{}", cask.base.token),
+                        Keg::Formula(formula, _) => println!("{}", formula.base.full_name()),
+                        Keg::Cask(cask, _) => println!("{}", cask.base.full_token()),
                     };
                 }
 
@@ -621,29 +992,25 @@ pub mod search {
 
             for keg in kegs {
                 match keg {
-                    Keg::Formula(formula, installed) => {
-                        let name = if installed.is_some() {
-                            format!("{} {}", formula.base.name, pretty::bool(true))
-                        } else {
-                            formula.base.name
-                        };
-
-                        formulae.push(name)
-                    }
-                    Keg::Cask(cask, installed) => {
-                        let name = if installed.is_some() {
-                            format!("{} {}", cask.base.token, pretty::bool(true))
-                        } else {
-                            cask.base.token
-                        };
-
-                        casks.push(name)
-                    }
+                    Keg::Formula(formula, installed) => formulae.push(table_name(
+                        &formula.base.full_name(),
+                        installed.is_some(),
+                        installed.as_ref().as_ref().is_some_and(|i| i.pinned),
+                        formula.base.status(),
+                    )),
+                    Keg::Cask(cask, installed) => casks.push(table_name(
+                        &cask.base.full_token(),
+                        installed.is_some(),
+                        installed.as_ref().as_ref().is_some_and(|i| i.pinned),
+                        cask.base.status(),
+                    )),
                 }
             }
 
-            formulae.sort_unstable();
-            casks.sort_unstable();
+            if self.rank_by.is_none() {
+                formulae.sort_unstable();
+                casks.sort_unstable();
+            }
 
             let formulae = pretty::table(&formulae, width);
             let casks = pretty::table(&casks, width);
@@ -665,7 +1032,7 @@ pub mod search {
             let mut kegs: Vec<Keg> = Vec::new();
 
             for formula in state.formulae.all.into_values() {
-                let name = formula.base.name.clone();
+                let name = formula.base.full_name();
                 let keg = Keg::Formula(
                     formula,
                     Box::new(state.formulae.installed.get(&name).cloned()),
@@ -675,8 +1042,8 @@ pub mod search {
             }
 
             for cask in state.casks.all.into_values() {
-                let token = cask.base.token.clone();
-                let keg = Keg::Cask(cask, state.casks.installed.get(&token).cloned());
+                let token = cask.base.full_token();
+                let keg = Keg::Cask(cask, Box::new(state.casks.installed.get(&token).cloned()));
 
                 kegs.push(keg);
             }
@@ -693,14 +1060,26 @@ pub mod search {
             models::formula::Formula,
             Box<Option<models::formula::installed::Formula>>,
         ),
-        Cask(models::cask::Cask, Option<models::cask::installed::Cask>),
+        Cask(
+            models::cask::Cask,
+            Box<Option<models::cask::installed::Cask>>,
+        ),
+    }
+
+    impl Keg {
+        fn analytics(&self) -> Option<&models::analytics::Analytics> {
+            match self {
+                Keg::Formula(formula, _) => formula.analytics.as_ref(),
+                Keg::Cask(cask, _) => cask.analytics.as_ref(),
+            }
+        }
     }
 
     impl SkimItem for Keg {
-        fn text(&self) -> Cow<str> {
+        fn text(&self) -> Cow<'_, str> {
             match self {
-                Keg::Formula(formula, _) => Cow::Borrowed(&formula.base.name),
-                Keg::Cask(cask, _) => Cow::Borrowed(&cask.base.token),
+                Keg::Formula(formula, _) => Cow::Owned(formula.base.full_name()),
+                Keg::Cask(cask, _) => Cow::Owned(cask.base.full_token()),
             }
         }
 
@@ -711,7 +1090,9 @@ pub mod search {
                 Keg::Formula(formula, installed) => {
                     info_formula(&mut w, formula, installed.as_ref().as_ref()).unwrap()
                 }
-                Keg::Cask(cask, installed) => info_cask(&mut w, cask, installed.as_ref()).unwrap(),
+                Keg::Cask(cask, installed) => {
+                    info_cask(&mut w, cask, installed.as_ref().as_ref()).unwrap()
+                }
             };
 
             let preview = String::from_utf8(w).unwrap();
@@ -765,40 +1146,425 @@ pub struct Exists {
 }
 
 impl Exists {
-    pub fn run(&self, state: State) -> bool {
-        let formulae = state.formulae.all;
-        let casks = state.casks.all;
+    pub fn run(&self, mut engine: Engine, format: Format) -> anyhow::Result<bool> {
+        engine.ensure_cache()?;
 
-        if self.cask {
-            return casks.contains_key(&self.name);
-        }
+        let kind = self.find(&engine)?;
+
+        if format.is_json() {
+            let mut buf = BufWriter::new(std::io::stdout());
+
+            let record = output::Exists {
+                name: &self.name,
+                exists: kind.is_some(),
+                kind,
+            };
+
+            output::write_one(&mut buf, format, &record)?;
 
-        if self.formula {
-            return formulae.contains_key(&self.name);
+            buf.flush()?;
         }
 
-        formulae.contains_key(&self.name) || casks.contains_key(&self.name)
+        Ok(kind.is_some())
+    }
+
+    fn find(&self, engine: &Engine) -> anyhow::Result<Option<&'static str>> {
+        let resolved = engine.resolve(&self.name, kind(self.formula, self.cask))?;
+
+        let kind = notice_resolved(&self.name, resolved).map(|r| match r.kind {
+            resolve::Kind::Formula => "formula",
+            resolve::Kind::Cask => "cask",
+        });
+
+        Ok(kind)
     }
 }
 
-pub mod install {
-    use std::borrow::Cow;
+pub mod deps {
     use std::io::{BufWriter, Write};
-    use std::ops::Deref;
 
     use clap::Args;
     use colored::Colorize;
-    use inquire::{Confirm, InquireError};
-    use skim::{ItemPreview, PreviewContext, SkimItem};
+    use terminal_size::{terminal_size, Width};
 
-    use brewer_core::models;
-    use brewer_engine::{Engine, State};
+    use brewer_core::deps::Graph;
+    use brewer_engine::State;
 
-    use crate::cli::{info_cask, info_formula, select_skim};
+    use crate::cli::formula_name;
+    use crate::output::{self, Format};
+    use crate::pretty;
     use crate::pretty::header;
 
     #[derive(Args)]
-    pub struct Install {
+    pub struct Deps {
+        pub name: String,
+
+        /// Show dependencies as a tree
+        #[clap(long, short, action)]
+        pub tree: bool,
+
+        /// Include build dependencies
+        #[clap(long, short = 'b', action)]
+        pub include_build: bool,
+    }
+
+    impl Deps {
+        pub fn run(&self, state: State, format: Format) -> anyhow::Result<bool> {
+            let name = formula_name(&state, &self.name);
+            let graph = Graph::new(&state.formulae.all);
+
+            if !graph.contains(&name) {
+                return Ok(false);
+            }
+
+            let mut buf = BufWriter::new(std::io::stdout());
+
+            if self.tree && format.is_json() {
+                let mut path = vec![name.as_str()];
+
+                let tree = self.dependency_tree(&graph, &state, &mut path);
+
+                output::write_one(&mut buf, format, &tree)?;
+            } else if self.tree {
+                writeln!(buf, "{}", name.cyan().bold())?;
+
+                let mut path = vec![name.as_str()];
+
+                self.write_tree(&mut buf, &graph, &state, &mut path, "")?;
+            } else {
+                let mut dependencies = graph.dependencies(&name, self.include_build);
+
+                dependencies.sort_unstable();
+
+                let header = format!("Dependencies of {name}");
+
+                write_names(&mut buf, format, &header, &graph, &state, dependencies)?;
+            }
+
+            buf.flush()?;
+
+            Ok(true)
+        }
+
+        fn dependency_tree<'a>(
+            &'a self,
+            graph: &'a Graph,
+            state: &State,
+            path: &mut Vec<&'a str>,
+        ) -> output::Dependency {
+            // path always contains at least the root formula
+            let name = *path.last().unwrap();
+
+            let mut dependencies = Vec::new();
+
+            for dependency in graph.direct_dependencies(name, self.include_build) {
+                if path.contains(&dependency) {
+                    continue;
+                }
+
+                path.push(dependency);
+                dependencies.push(self.dependency_tree(graph, state, path));
+                path.pop();
+            }
+
+            output::Dependency {
+                name: name.to_string(),
+                installed: state.formulae.installed.contains_key(name),
+                dependencies: Some(dependencies),
+            }
+        }
+
+        fn write_tree<'a>(
+            &'a self,
+            w: &mut impl Write,
+            graph: &'a Graph,
+            state: &State,
+            path: &mut Vec<&'a str>,
+            prefix: &str,
+        ) -> anyhow::Result<()> {
+            // path always contains at least the root formula
+            let name = *path.last().unwrap();
+            let dependencies = graph.direct_dependencies(name, self.include_build);
+
+            for (i, dependency) in dependencies.iter().enumerate() {
+                let (branch, indent) = if i == dependencies.len() - 1 {
+                    ("└── ", "    ")
+                } else {
+                    ("├── ", "│   ")
+                };
+
+                let label = label(graph, state, dependency);
+
+                if path.contains(dependency) {
+                    writeln!(w, "{prefix}{branch}{label} {}", "(cycle)".yellow())?;
+                    continue;
+                }
+
+                writeln!(w, "{prefix}{branch}{label}")?;
+
+                path.push(dependency);
+                self.write_tree(w, graph, state, path, &format!("{prefix}{indent}"))?;
+                path.pop();
+            }
+
+            Ok(())
+        }
+    }
+
+    #[derive(Args)]
+    pub struct Uses {
+        pub name: String,
+
+        /// Show only installed formulae
+        #[clap(long, short, action)]
+        pub installed: bool,
+
+        /// Include formulae which need the given formula only to build
+        #[clap(long, short = 'b', action)]
+        pub include_build: bool,
+    }
+
+    impl Uses {
+        pub fn run(&self, state: State, format: Format) -> anyhow::Result<bool> {
+            let name = formula_name(&state, &self.name);
+            let graph = Graph::new(&state.formulae.all);
+
+            let dependents = graph.dependents(&name, self.include_build);
+
+            if !graph.contains(&name) && dependents.is_empty() {
+                return Ok(false);
+            }
+
+            let dependents: Vec<_> = dependents
+                .into_iter()
+                .filter(|d| !self.installed || state.formulae.installed.contains_key(d))
+                .collect();
+
+            let mut buf = BufWriter::new(std::io::stdout());
+
+            let header = format!("Formulae which use {name}");
+
+            write_names(&mut buf, format, &header, &graph, &state, dependents)?;
+
+            buf.flush()?;
+
+            Ok(true)
+        }
+    }
+
+    fn write_names(
+        w: &mut impl Write,
+        format: Format,
+        header: &str,
+        graph: &Graph,
+        state: &State,
+        names: Vec<String>,
+    ) -> anyhow::Result<()> {
+        if format.is_json() {
+            let records: Vec<_> = names
+                .into_iter()
+                .map(|name| output::Dependency {
+                    installed: state.formulae.installed.contains_key(&name),
+                    name,
+                    dependencies: None,
+                })
+                .collect();
+
+            return output::write(w, format, &records);
+        }
+
+        if format == Format::Plain {
+            for name in names {
+                writeln!(w, "{name}")?;
+            }
+
+            return Ok(());
+        }
+
+        let width = terminal_size().map(|(Width(w), _)| w).unwrap_or(80);
+
+        let names: Vec<_> = names
+            .into_iter()
+            .map(|name| label(graph, state, &name))
+            .collect();
+
+        writeln!(w, "{}", header::primary!("{header}"))?;
+        pretty::table(&names, width).print(w)?;
+
+        Ok(())
+    }
+
+    fn label(graph: &Graph, state: &State, name: &str) -> String {
+        if !graph.contains(name) {
+            format!("{name} {}", "(unknown)".yellow())
+        } else if state.formulae.installed.contains_key(name) {
+            format!("{name} {}", pretty::bool(true))
+        } else {
+            name.to_string()
+        }
+    }
+}
+
+pub mod outdated {
+    use std::io::{BufWriter, Write};
+
+    use clap::Args;
+    use colored::Colorize;
+    use prettytable::format::consts::FORMAT_CLEAN;
+    use prettytable::{row, Table};
+
+    use brewer_core::outdated;
+    use brewer_engine::State;
+
+    use crate::output::{self, Format};
+    use crate::pretty;
+    use crate::pretty::header;
+
+    #[derive(Args)]
+    pub struct Outdated {
+        /// Show only formulae
+        #[clap(short, long, action, group = "type")]
+        pub formula: bool,
+
+        /// Show only casks
+        #[clap(short, long, action, group = "type")]
+        pub cask: bool,
+    }
+
+    struct Report {
+        formulae: Vec<outdated::Formula>,
+        casks: Vec<outdated::Cask>,
+    }
+
+    impl Outdated {
+        /// Returns false if anything is outdated
+        pub fn run(&self, state: State, format: Format) -> anyhow::Result<bool> {
+            let report = Report {
+                formulae: if self.cask {
+                    Vec::new()
+                } else {
+                    outdated::formulae(&state.formulae.installed)
+                },
+                casks: if self.formula {
+                    Vec::new()
+                } else {
+                    outdated::casks(&state.casks.installed)
+                },
+            };
+
+            let up_to_date = report.formulae.is_empty() && report.casks.is_empty();
+
+            let mut buf = BufWriter::new(std::io::stdout());
+
+            match format {
+                Format::Json | Format::Ndjson => {
+                    let formulae = report.formulae.iter().map(output::Outdated::Formula);
+                    let casks = report.casks.iter().map(output::Outdated::Cask);
+
+                    let records: Vec<_> = formulae.chain(casks).collect();
+
+                    output::write(&mut buf, format, &records)?;
+                }
+                Format::Plain => {
+                    for f in report.formulae {
+                        writeln!(buf, "{}", f.name)?;
+                    }
+
+                    for c in report.casks {
+                        writeln!(buf, "{}", c.token)?;
+                    }
+                }
+                Format::Table => self.write_table(&mut buf, report)?,
+            }
+
+            buf.flush()?;
+
+            Ok(up_to_date)
+        }
+
+        fn write_table(&self, w: &mut impl Write, report: Report) -> anyhow::Result<()> {
+            if !report.formulae.is_empty() {
+                writeln!(w, "{}", header::primary!("Formulae"))?;
+
+                let mut table = table();
+
+                for f in report.formulae {
+                    table.add_row(row![
+                        f.name.cyan(),
+                        f.installed,
+                        "→",
+                        f.current.green(),
+                        pinned(f.pinned)
+                    ]);
+                }
+
+                table.print(w)?;
+            }
+
+            if !report.casks.is_empty() {
+                writeln!(w, "{}", header::primary!("Casks"))?;
+
+                let mut table = table();
+
+                for c in report.casks {
+                    table.add_row(row![
+                        c.token.cyan(),
+                        c.installed.join(", "),
+                        "→",
+                        c.current.green(),
+                        pinned(c.pinned)
+                    ]);
+                }
+
+                table.print(w)?;
+            }
+
+            Ok(())
+        }
+    }
+
+    fn table() -> Table {
+        let mut table = Table::new();
+        let mut format = *FORMAT_CLEAN;
+
+        format.padding(0, 2);
+        table.set_format(format);
+
+        table
+    }
+
+    fn pinned(pinned: bool) -> String {
+        if pinned {
+            pretty::pinned()
+        } else {
+            String::new()
+        }
+    }
+}
+
+pub mod install {
+    use std::borrow::Cow;
+    use std::collections::HashSet;
+    use std::io::{BufWriter, Write};
+    use std::ops::Deref;
+
+    use clap::Args;
+    use colored::Colorize;
+    use inquire::{Confirm, InquireError};
+    use skim::{ItemPreview, PreviewContext, SkimItem};
+
+    use brewer_core::deps::Graph;
+    use brewer_core::models;
+    use brewer_core::models::keg::Status;
+    use brewer_core::resolve::{Kind, Resolver};
+    use brewer_engine::{Engine, State};
+
+    use crate::cli::{info_cask, info_formula, kind, notice_resolved, select_skim};
+    use crate::pretty;
+    use crate::pretty::header;
+
+    #[derive(Args)]
+    pub struct Install {
         pub names: Vec<String>,
 
         #[clap(short, long, action, group = "type")]
@@ -810,18 +1576,22 @@ pub mod install {
         /// Confirm
         #[clap(short, long, action)]
         pub yes: bool,
+
+        /// Install deprecated formulae and casks
+        #[clap(long, action)]
+        pub allow_deprecated: bool,
     }
 
     impl Install {
         pub fn run(&self, mut engine: Engine) -> anyhow::Result<()> {
             let state = engine.cache_or_latest()?;
 
-            let kegs = self.get_kegs(state)?;
+            let kegs = self.get_kegs(&state)?;
 
             if kegs.is_empty() {
                 Ok(())
             } else {
-                if self.yes || plan(&kegs)? {
+                if self.yes || plan(&kegs, &state)? {
                     engine.install(kegs)?;
                 }
 
@@ -829,7 +1599,7 @@ pub mod install {
             }
         }
 
-        fn get_kegs(&self, state: State) -> anyhow::Result<Vec<models::Keg>> {
+        fn get_kegs(&self, state: &State) -> anyhow::Result<Vec<models::Keg>> {
             if self.names.is_empty() {
                 self.get_kegs_from_skim(state)
             } else {
@@ -837,82 +1607,94 @@ pub mod install {
             }
         }
 
-        fn get_kegs_from_args(&self, mut state: State) -> anyhow::Result<Vec<models::Keg>> {
+        fn get_kegs_from_args(&self, state: &State) -> anyhow::Result<Vec<models::Keg>> {
+            let resolver = Resolver::new(&state.formulae.all, &state.casks.all);
+            let kind = kind(self.formula, self.cask);
+
             let mut kegs = Vec::new();
 
-            for name in &self.names {
-                let keg = if self.formula {
-                    if state.formulae.installed.contains_key(name) {
-                        println!(
-                            "{}",
-                            header::warning!("Formula {name} is already installed, skipping")
-                        );
-                        continue;
-                    }
+            for given in &self.names {
+                let Some(resolved) = notice_resolved(given, resolver.resolve(given, kind)) else {
+                    println!(
+                        "{}",
+                        header::warning!("Unknown formula or cask {given}, skipping")
+                    );
+                    continue;
+                };
 
-                    state.formulae.all.remove(name).map(models::Keg::Formula)
-                } else if self.cask {
-                    if state.casks.installed.contains_key(name) {
-                        println!(
-                            "{}",
-                            header::warning!("Cask {name} is already installed, skipping")
-                        );
-                        continue;
-                    }
+                let name = &resolved.name;
 
-                    state.casks.all.remove(name).map(models::Keg::Cask)
-                } else {
-                    if state.formulae.installed.contains_key(name) {
-                        println!(
-                            "{}",
-                            header::warning!("Formula {name} is already installed, skipping")
-                        );
-                        continue;
-                    }
+                let keg = match resolved.kind {
+                    Kind::Formula => {
+                        if state.formulae.installed.contains_key(name) {
+                            println!(
+                                "{}",
+                                header::warning!("Formula {name} is already installed, skipping")
+                            );
+                            continue;
+                        }
 
-                    if state.casks.installed.contains_key(name) {
-                        println!(
-                            "{}",
-                            header::warning!("Cask {name} is already installed, skipping")
-                        );
-                        continue;
+                        state
+                            .formulae
+                            .all
+                            .get(name)
+                            .cloned()
+                            .map(models::Keg::Formula)
                     }
+                    Kind::Cask => {
+                        if state.casks.installed.contains_key(name) {
+                            println!(
+                                "{}",
+                                header::warning!("Cask {name} is already installed, skipping")
+                            );
+                            continue;
+                        }
 
-                    state
-                        .formulae
-                        .all
-                        .remove(name)
-                        .map(models::Keg::Formula)
-                        .or_else(|| state.casks.all.remove(name).map(models::Keg::Cask))
+                        state.casks.all.get(name).cloned().map(models::Keg::Cask)
+                    }
                 };
 
+                // the resolver only returns names from the state
                 let Some(keg) = keg else {
-                    println!(
-                        "{}",
-                        header::warning!("Unknown formula or cask {name}, skipping")
-                    );
                     continue;
                 };
 
+                if !is_allowed(&keg, self.allow_deprecated) {
+                    continue;
+                }
+
                 kegs.push(keg);
             }
 
             Ok(kegs)
         }
 
-        fn get_kegs_from_skim(&self, state: State) -> anyhow::Result<Vec<models::Keg>> {
+        fn get_kegs_from_skim(&self, state: &State) -> anyhow::Result<Vec<models::Keg>> {
             let mut non_installed: Vec<Keg> =
                 Vec::with_capacity(state.formulae.all.len() + state.casks.all.len());
 
-            for formula in state.formulae.all.into_values() {
-                if !state.formulae.installed.contains_key(&formula.base.name) {
-                    non_installed.push(formula.into());
+            let allowed = |status: Status| match status {
+                Status::Active => true,
+                Status::Deprecated(_) => self.allow_deprecated,
+                Status::Disabled(_) => false,
+            };
+
+            for formula in state.formulae.all.values() {
+                if !state
+                    .formulae
+                    .installed
+                    .contains_key(&formula.base.full_name())
+                    && allowed(formula.base.status())
+                {
+                    non_installed.push(formula.clone().into());
                 }
             }
 
-            for cask in state.casks.all.into_values() {
-                if !state.casks.installed.contains_key(&cask.base.token) {
-                    non_installed.push(cask.into());
+            for cask in state.casks.all.values() {
+                if !state.casks.installed.contains_key(&cask.base.full_token())
+                    && allowed(cask.base.status())
+                {
+                    non_installed.push(cask.clone().into());
                 }
             }
 
@@ -925,7 +1707,35 @@ pub mod install {
         }
     }
 
-    fn plan(kegs: &Vec<models::Keg>) -> anyhow::Result<bool> {
+    /// Disabled kegs are never installed, deprecated ones only if explicitly allowed
+    pub(crate) fn is_allowed(keg: &models::Keg, allow_deprecated: bool) -> bool {
+        let name = keg.name();
+        let reason = keg
+            .status()
+            .reason()
+            .map(|r| format!(" ({r})"))
+            .unwrap_or_default();
+
+        match keg.status() {
+            Status::Active => true,
+            Status::Deprecated(_) if allow_deprecated => true,
+            Status::Deprecated(_) => {
+                eprintln!(
+                    "{}",
+                    header::warning!(
+                        "{name} is deprecated{reason}, pass --allow-deprecated to install it anyway"
+                    )
+                );
+                false
+            }
+            Status::Disabled(_) => {
+                eprintln!("{}", header::error!("{name} is disabled{reason}, skipping"));
+                false
+            }
+        }
+    }
+
+    pub(crate) fn plan(kegs: &[models::Keg], state: &State) -> anyhow::Result<bool> {
         let mut w = BufWriter::new(std::io::stderr());
 
         writeln!(
@@ -935,43 +1745,139 @@ pub mod install {
         )?;
 
         for keg in kegs {
+            let status = pretty::status(keg.status())
+                .map(|s| format!(" {s}"))
+                .unwrap_or_default();
+
             match &keg {
                 models::Keg::Formula(f) => writeln!(
                     w,
-                    "{} {} (Formula)",
+                    "{} {} (Formula){status}",
                     f.base.name.cyan(),
                     f.base.versions.stable
                 )?,
-                models::Keg::Cask(c) => {
-                    writeln!(w, "{} {} (Cask)", c.base.token.cyan(), c.base.version)?
-                }
+                models::Keg::Cask(c) => writeln!(
+                    w,
+                    "{} {} (Cask){status}",
+                    c.base.token.cyan(),
+                    c.base.version
+                )?,
             }
         }
 
         writeln!(w)?;
 
-        let mut executables: Vec<String> = Vec::new();
+        let requested: Vec<String> = kegs
+            .iter()
+            .filter_map(|k| match k {
+                models::Keg::Formula(f) => Some(f.base.full_name()),
+                models::Keg::Cask(_) => None,
+            })
+            .collect();
+        let requested: HashSet<&str> = requested.iter().map(String::as_str).collect();
 
-        for k in kegs {
-            if let models::Keg::Formula(f) = &k {
-                for e in &f.executables {
-                    executables.push(e.purple().to_string());
-                }
+        let graph = Graph::new(&state.formulae.all);
+
+        let mut dependencies: Vec<&models::formula::Formula> = Vec::new();
+        let mut installed: Vec<String> = Vec::new();
+        let mut unknown: Vec<String> = Vec::new();
+
+        for name in graph.topological_order(requested.iter().copied(), false) {
+            if requested.contains(name.as_str()) {
+                continue;
+            }
+
+            if state.formulae.installed.contains_key(&name) {
+                installed.push(name);
+            } else if let Some(formula) = state.formulae.all.get(&name) {
+                dependencies.push(formula);
+            } else {
+                unknown.push(name);
             }
         }
 
-        if !executables.is_empty() {
+        if !dependencies.is_empty() {
             writeln!(
                 w,
                 "{}",
-                header::primary!("The following executables will be provided")
+                header::primary!("The following dependencies will be installed")
             )?;
-            writeln!(w, "{}", executables.join(" "))?;
+
+            for f in &dependencies {
+                writeln!(
+                    w,
+                    "{} {} (Formula)",
+                    f.base.name.cyan(),
+                    f.base.versions.stable
+                )?;
+            }
+
             writeln!(w)?;
         }
 
-        w.flush()?;
-
+        if !installed.is_empty() {
+            writeln!(
+                w,
+                "{}",
+                header::primary!("The following dependencies are already installed")
+            )?;
+            writeln!(w, "{}", installed.join(" "))?;
+            writeln!(w)?;
+        }
+
+        if !unknown.is_empty() {
+            writeln!(
+                w,
+                "{}",
+                header::warning!("The following dependencies are unknown")
+            )?;
+            writeln!(w, "{}", unknown.join(" "))?;
+            writeln!(w)?;
+        }
+
+        let caveats = kegs
+            .iter()
+            .filter_map(|k| Some((k.name(), k.caveats()?)))
+            .chain(
+                dependencies
+                    .iter()
+                    .filter_map(|f| Some((f.base.name.as_str(), f.base.caveats.as_deref()?))),
+            );
+
+        for (name, caveats) in caveats {
+            writeln!(w, "{}", header::primary!("Caveats of {name}"))?;
+            writeln!(w, "{}", caveats.trim_end())?;
+            writeln!(w)?;
+        }
+
+        let mut executables: Vec<String> = Vec::new();
+
+        let formulae = kegs
+            .iter()
+            .filter_map(|k| match k {
+                models::Keg::Formula(f) => Some(f),
+                models::Keg::Cask(_) => None,
+            })
+            .chain(dependencies);
+
+        for f in formulae {
+            for e in &f.executables {
+                executables.push(e.purple().to_string());
+            }
+        }
+
+        if !executables.is_empty() {
+            writeln!(
+                w,
+                "{}",
+                header::primary!("The following executables will be provided")
+            )?;
+            writeln!(w, "{}", executables.join(" "))?;
+            writeln!(w)?;
+        }
+
+        w.flush()?;
+
         let result = Confirm::new("Proceed?").with_default(false).prompt();
 
         match result {
@@ -1007,7 +1913,7 @@ pub mod install {
     }
 
     impl SkimItem for Keg {
-        fn text(&self) -> Cow<str> {
+        fn text(&self) -> Cow<'_, str> {
             match &self.0 {
                 models::Keg::Formula(formula) => Cow::Borrowed(&formula.base.name),
                 models::Keg::Cask(cask) => Cow::Borrowed(&cask.base.token),
@@ -1024,243 +1930,1536 @@ pub mod install {
 
             let preview = String::from_utf8(buf).unwrap();
 
-            ItemPreview::AnsiText(preview)
+            ItemPreview::AnsiText(preview)
+        }
+    }
+}
+
+pub mod uninstall {
+    use std::borrow::Cow;
+    use std::collections::{HashMap, HashSet};
+    use std::io::{BufWriter, Write};
+
+    use clap::Args;
+    use colored::Colorize;
+    use inquire::{Confirm, InquireError};
+    use skim::{ItemPreview, PreviewContext, SkimItem};
+
+    use brewer_core::deps::Graph;
+    use brewer_core::models;
+    use brewer_core::resolve::{Kind, Resolved, Resolver};
+    use brewer_engine::{Engine, State};
+
+    use crate::cli::{info_cask, info_formula, kind, notice_resolved, select_skim};
+    use crate::pretty::header;
+
+    #[derive(Args)]
+    pub struct Uninstall {
+        pub names: Vec<String>,
+
+        #[clap(short, long, action, group = "type")]
+        pub formula: bool,
+
+        #[clap(short, long, action, group = "type")]
+        pub cask: bool,
+
+        /// Confirm
+        #[clap(short, long, action)]
+        pub yes: bool,
+
+        /// Uninstall even if other installed formulae depend on the given ones
+        #[clap(long, action)]
+        pub ignore_dependencies: bool,
+
+        /// Also uninstall dependencies which are no longer needed
+        #[clap(long, action)]
+        pub autoremove: bool,
+    }
+
+    impl Uninstall {
+        pub fn run(&self, mut engine: Engine) -> anyhow::Result<bool> {
+            let state = engine.cache_or_latest()?;
+
+            let kegs = self.get_kegs(&state)?;
+
+            if kegs.is_empty() {
+                return Ok(true);
+            }
+
+            let graph = Graph::new(&state.formulae.all);
+
+            let removing: Vec<String> = kegs
+                .iter()
+                .filter_map(|k| match k {
+                    Keg::Formula(formula) => Some(formula.upstream.base.full_name()),
+                    Keg::Cask(_) => None,
+                })
+                .collect();
+            let removing: HashSet<&str> = removing.iter().map(String::as_str).collect();
+
+            let mut blocking: HashMap<&str, Vec<&str>> = HashMap::new();
+
+            for name in &removing {
+                let dependents: Vec<_> = graph
+                    .direct_dependents(name, false)
+                    .into_iter()
+                    .filter(|d| state.formulae.installed.contains_key(*d))
+                    .filter(|d| !removing.contains(d))
+                    .collect();
+
+                if !dependents.is_empty() {
+                    blocking.insert(name, dependents);
+                }
+            }
+
+            if !blocking.is_empty() && !self.ignore_dependencies {
+                for (name, dependents) in blocking {
+                    eprintln!(
+                        "{}",
+                        header::error!(
+                            "Refusing to uninstall {name} because it is required by {}",
+                            dependents.join(", ")
+                        )
+                    );
+                }
+
+                eprintln!("Use --ignore-dependencies to uninstall anyway");
+
+                return Ok(false);
+            }
+
+            let orphans: Vec<_> = graph
+                .orphans(&state.formulae.installed, &removing)
+                .into_iter()
+                .filter_map(|name| state.formulae.installed.get(&name))
+                .collect();
+
+            if self.yes || plan(&kegs, &blocking, &orphans, self.autoremove)? {
+                let mut kegs: Vec<models::Keg> = kegs
+                    .into_iter()
+                    .map(|k| match k {
+                        Keg::Formula(formula) => formula.upstream.into(),
+                        Keg::Cask(cask) => cask.upstream.into(),
+                    })
+                    .collect();
+
+                if self.autoremove {
+                    kegs.extend(orphans.into_iter().map(|f| f.upstream.clone().into()));
+                }
+
+                engine.uninstall(kegs, self.ignore_dependencies)?;
+            }
+
+            Ok(true)
+        }
+
+        fn get_kegs(&self, state: &State) -> anyhow::Result<Vec<Keg>> {
+            if self.names.is_empty() {
+                self.get_kegs_from_skim(state)
+            } else {
+                self.get_kegs_from_args(state)
+            }
+        }
+
+        fn get_kegs_from_args(&self, state: &State) -> anyhow::Result<Vec<Keg>> {
+            let resolver = Resolver::new(&state.formulae.all, &state.casks.all);
+            let kind = kind(self.formula, self.cask);
+
+            let mut kegs = Vec::new();
+
+            for given in &self.names {
+                let resolved = match resolver.resolve(given, kind) {
+                    // a formula and a cask might share the name, prefer the installed one
+                    Some(formula)
+                        if kind.is_none()
+                            && formula.kind == Kind::Formula
+                            && !state.formulae.installed.contains_key(&formula.name) =>
+                    {
+                        resolver
+                            .resolve(given, Some(Kind::Cask))
+                            .filter(|cask| state.casks.installed.contains_key(&cask.name))
+                            .or(Some(formula))
+                    }
+                    resolved => resolved,
+                };
+
+                let keg = match notice_resolved(given, resolved) {
+                    Some(Resolved {
+                        kind: Kind::Formula,
+                        name,
+                        ..
+                    }) => {
+                        let Some(installed) = state.formulae.installed.get(&name) else {
+                            println!(
+                                "{}",
+                                header::warning!("Formula {name} is not installed, skipping")
+                            );
+                            continue;
+                        };
+
+                        Keg::Formula(installed.clone())
+                    }
+                    Some(Resolved {
+                        kind: Kind::Cask,
+                        name,
+                        ..
+                    }) => {
+                        let Some(installed) = state.casks.installed.get(&name) else {
+                            println!(
+                                "{}",
+                                header::warning!("Cask {name} is not installed, skipping")
+                            );
+                            continue;
+                        };
+
+                        Keg::Cask(installed.clone())
+                    }
+                    None => {
+                        println!(
+                            "{}",
+                            header::warning!("Formula or cask {given} is not installed, skipping")
+                        );
+                        continue;
+                    }
+                };
+
+                kegs.push(keg);
+            }
+
+            Ok(kegs)
+        }
+
+        fn get_kegs_from_skim(&self, state: &State) -> anyhow::Result<Vec<Keg>> {
+            let mut installed: Vec<Keg> =
+                Vec::with_capacity(state.formulae.installed.len() + state.casks.installed.len());
+
+            for formula in state
+                .formulae
+                .installed
+                .values()
+                .filter(|f| f.receipt.installed_on_request)
+            {
+                installed.push(formula.clone().into());
+            }
+
+            for cask in state.casks.installed.values() {
+                installed.push(cask.clone().into());
+            }
+
+            let selected = select_skim(installed, "Uninstall", true)?
+                .into_iter()
+                .collect();
+
+            Ok(selected)
+        }
+    }
+
+    fn plan(
+        kegs: &[Keg],
+        blocking: &HashMap<&str, Vec<&str>>,
+        orphans: &[&models::formula::installed::Formula],
+        autoremove: bool,
+    ) -> anyhow::Result<bool> {
+        let mut w = BufWriter::new(std::io::stderr());
+
+        writeln!(
+            w,
+            "{}",
+            header::primary!("The following kegs will be uninstalled")
+        )?;
+
+        for keg in kegs {
+            match &keg {
+                Keg::Formula(f) => {
+                    writeln!(w, "{} {} (Formula)", f.upstream.base.name.cyan(), f.version)?
+                }
+                Keg::Cask(c) => {
+                    let mut versions: Vec<_> = c.versions.iter().map(String::as_str).collect();
+
+                    versions.sort_unstable();
+
+                    writeln!(
+                        w,
+                        "{} {} (Cask)",
+                        c.upstream.base.token.cyan(),
+                        versions.join(", ")
+                    )?
+                }
+            }
+        }
+
+        writeln!(w)?;
+
+        if !blocking.is_empty() {
+            writeln!(
+                w,
+                "{}",
+                header::warning!("The following formulae depend on kegs being uninstalled")
+            )?;
+
+            for (name, dependents) in blocking {
+                writeln!(w, "{} (requires {name})", dependents.join(" ").yellow())?;
+            }
+
+            writeln!(w)?;
+        }
+
+        if !orphans.is_empty() {
+            if autoremove {
+                writeln!(
+                    w,
+                    "{}",
+                    header::primary!("The following unused dependencies will be uninstalled")
+                )?;
+            } else {
+                writeln!(
+                    w,
+                    "{}",
+                    header::warning!("The following dependencies will no longer be needed")
+                )?;
+            }
+
+            for f in orphans {
+                writeln!(w, "{} {} (Formula)", f.upstream.base.name.cyan(), f.version)?;
+            }
+
+            if !autoremove {
+                writeln!(w, "Use --autoremove to uninstall them as well")?;
+            }
+
+            writeln!(w)?;
+        }
+
+        let mut executables: Vec<String> = Vec::new();
+
+        let formulae = kegs
+            .iter()
+            .filter_map(|k| match k {
+                Keg::Formula(f) => Some(f),
+                Keg::Cask(_) => None,
+            })
+            .chain(orphans.iter().copied().filter(|_| autoremove));
+
+        for f in formulae {
+            for e in &f.upstream.executables {
+                executables.push(e.purple().to_string());
+            }
+        }
+
+        if !executables.is_empty() {
+            writeln!(
+                w,
+                "{}",
+                header::primary!("The following executables will be removed")
+            )?;
+            writeln!(w, "{}", executables.join(" "))?;
+            writeln!(w)?;
+        }
+
+        w.flush()?;
+
+        let result = Confirm::new("Proceed?").with_default(false).prompt();
+
+        match result {
+            Ok(value) => Ok(value),
+            Err(e) => match e {
+                InquireError::OperationCanceled => Ok(false),
+                e => Err(e.into()),
+            },
+        }
+    }
+
+    #[derive(Clone)]
+    pub enum Keg {
+        Formula(models::formula::installed::Formula),
+        Cask(models::cask::installed::Cask),
+    }
+
+    impl From<models::formula::installed::Formula> for Keg {
+        fn from(value: models::formula::installed::Formula) -> Self {
+            Keg::Formula(value)
+        }
+    }
+
+    impl From<models::cask::installed::Cask> for Keg {
+        fn from(value: models::cask::installed::Cask) -> Self {
+            Keg::Cask(value)
+        }
+    }
+
+    impl SkimItem for Keg {
+        fn text(&self) -> Cow<'_, str> {
+            match &self {
+                Keg::Formula(formula) => Cow::Borrowed(&formula.upstream.base.name),
+                Keg::Cask(cask) => Cow::Borrowed(&cask.upstream.base.token),
+            }
+        }
+
+        fn preview(&self, _context: PreviewContext) -> ItemPreview {
+            let mut buf = Vec::new();
+
+            match &self {
+                Keg::Formula(formula) => {
+                    info_formula(&mut buf, &formula.upstream, Some(formula)).unwrap()
+                }
+                Keg::Cask(cask) => info_cask(&mut buf, &cask.upstream, Some(cask)).unwrap(),
+            };
+
+            let preview = String::from_utf8(buf).unwrap();
+
+            ItemPreview::AnsiText(preview)
+        }
+    }
+}
+
+pub mod upgrade {
+    use std::borrow::Cow;
+    use std::io::{BufWriter, Write};
+
+    use clap::Args;
+    use colored::Colorize;
+    use inquire::{Confirm, InquireError};
+    use skim::{ItemPreview, PreviewContext, SkimItem};
+
+    use brewer_core::resolve::Resolver;
+    use brewer_core::{models, outdated};
+    use brewer_engine::{Engine, State};
+
+    use crate::cli::{info_cask, info_formula, kind, notice_resolved, select_skim};
+    use crate::pretty::header;
+
+    #[derive(Args)]
+    pub struct Upgrade {
+        pub names: Vec<String>,
+
+        #[clap(short, long, action, group = "type")]
+        pub formula: bool,
+
+        #[clap(short, long, action, group = "type")]
+        pub cask: bool,
+
+        /// Confirm
+        #[clap(short, long, action)]
+        pub yes: bool,
+    }
+
+    impl Upgrade {
+        pub fn run(&self, mut engine: Engine) -> anyhow::Result<()> {
+            engine.ensure_pins()?;
+
+            let state = engine.cache_or_latest()?;
+
+            let names = self.resolve_names(&state);
+
+            let (outdated, held) = self.get_outdated(&state);
+
+            let held: Vec<_> = if names.is_empty() {
+                held
+            } else {
+                held.into_iter()
+                    .filter(|k| names.iter().any(|n| n == k.name()))
+                    .collect()
+            };
+
+            let kegs = if !names.is_empty() {
+                self.get_kegs_from_args(&state, &names, outdated, &held)
+            } else if !outdated.is_empty() {
+                select_skim(outdated, "Upgrade", true)?
+            } else {
+                println!("{}", header::primary!("Everything is up-to-date"));
+                Vec::new()
+            };
+
+            // otherwise the plan lists the pinned kegs
+            if kegs.is_empty() || self.yes {
+                for keg in &held {
+                    println!("{}", header::warning!("{} is pinned, skipping", keg.name()));
+                }
+            }
+
+            if !kegs.is_empty() && (self.yes || plan(&kegs, &held)?) {
+                engine.upgrade(kegs.into_iter().map(Keg::into_keg).collect())?;
+            }
+
+            Ok(())
+        }
+
+        /// Canonical names of the given names, names which do not resolve are kept as given
+        fn resolve_names(&self, state: &State) -> Vec<String> {
+            let resolver = Resolver::new(&state.formulae.all, &state.casks.all);
+            let kind = kind(self.formula, self.cask);
+
+            self.names
+                .iter()
+                .map(
+                    |given| match notice_resolved(given, resolver.resolve(given, kind)) {
+                        Some(resolved) => resolved.name,
+                        None => given.clone(),
+                    },
+                )
+                .collect()
+        }
+
+        /// Outdated kegs split into upgradable and pinned ones
+        fn get_outdated(&self, state: &State) -> (Vec<Keg>, Vec<Keg>) {
+            let mut kegs = Vec::new();
+
+            if !self.cask {
+                for outdated in outdated::formulae(&state.formulae.installed) {
+                    let Some(installed) = state.formulae.installed.get(&outdated.name) else {
+                        continue;
+                    };
+
+                    kegs.push(Keg::Formula(outdated, Box::new(installed.clone())));
+                }
+            }
+
+            if !self.formula {
+                for outdated in outdated::casks(&state.casks.installed) {
+                    let Some(installed) = state.casks.installed.get(&outdated.token) else {
+                        continue;
+                    };
+
+                    kegs.push(Keg::Cask(outdated, Box::new(installed.clone())));
+                }
+            }
+
+            kegs.into_iter().partition(|k| match k {
+                Keg::Formula(outdated, _) => !outdated.pinned,
+                Keg::Cask(outdated, _) => !outdated.pinned,
+            })
+        }
+
+        fn get_kegs_from_args(
+            &self,
+            state: &State,
+            names: &[String],
+            outdated: Vec<Keg>,
+            held: &[Keg],
+        ) -> Vec<Keg> {
+            let mut kegs = Vec::new();
+
+            for name in names {
+                if let Some(keg) = outdated.iter().find(|k| k.name() == name) {
+                    kegs.push(keg.clone());
+                    continue;
+                }
+
+                // pinned kegs are listed in the plan
+                if held.iter().any(|k| k.name() == name) {
+                    continue;
+                }
+
+                let formula = !self.cask && state.formulae.installed.contains_key(name);
+                let cask = !self.formula && state.casks.installed.contains_key(name);
+
+                let message = if formula || cask {
+                    format!("{name} is already up-to-date, skipping")
+                } else {
+                    format!("Formula or cask {name} is not installed, skipping")
+                };
+
+                println!("{}", header::warning!("{message}"));
+            }
+
+            kegs
+        }
+    }
+
+    fn plan(kegs: &[Keg], held: &[Keg]) -> anyhow::Result<bool> {
+        let mut w = BufWriter::new(std::io::stderr());
+
+        writeln!(
+            w,
+            "{}",
+            header::primary!("The following kegs will be upgraded")
+        )?;
+
+        for keg in kegs {
+            writeln!(w, "{}", keg.upgrade())?;
+        }
+
+        writeln!(w)?;
+
+        if !held.is_empty() {
+            writeln!(
+                w,
+                "{}",
+                header::warning!("The following pinned kegs will not be upgraded")
+            )?;
+
+            for keg in held {
+                writeln!(w, "{}", keg.upgrade())?;
+            }
+
+            writeln!(w)?;
+        }
+
+        w.flush()?;
+
+        let result = Confirm::new("Proceed?").with_default(false).prompt();
+
+        match result {
+            Ok(value) => Ok(value),
+            Err(e) => match e {
+                InquireError::OperationCanceled => Ok(false),
+                e => Err(e.into()),
+            },
+        }
+    }
+
+    #[derive(Clone)]
+    enum Keg {
+        Formula(outdated::Formula, Box<models::formula::installed::Formula>),
+        Cask(outdated::Cask, Box<models::cask::installed::Cask>),
+    }
+
+    impl Keg {
+        fn name(&self) -> &str {
+            match self {
+                Keg::Formula(f, _) => &f.name,
+                Keg::Cask(c, _) => &c.token,
+            }
+        }
+
+        fn upgrade(&self) -> String {
+            match self {
+                Keg::Formula(f, _) => format!(
+                    "{} {} → {} (Formula)",
+                    f.name.cyan(),
+                    f.installed,
+                    f.current.green()
+                ),
+                Keg::Cask(c, _) => format!(
+                    "{} {} → {} (Cask)",
+                    c.token.cyan(),
+                    c.installed.join(", "),
+                    c.current.green()
+                ),
+            }
+        }
+
+        fn into_keg(self) -> models::Keg {
+            match self {
+                Keg::Formula(_, installed) => installed.upstream.into(),
+                Keg::Cask(_, installed) => installed.upstream.into(),
+            }
+        }
+    }
+
+    impl SkimItem for Keg {
+        fn text(&self) -> Cow<'_, str> {
+            Cow::Borrowed(self.name())
+        }
+
+        fn preview(&self, _context: PreviewContext) -> ItemPreview {
+            let mut buf = Vec::new();
+
+            writeln!(buf, "{}", self.upgrade()).unwrap();
+            writeln!(buf).unwrap();
+
+            match &self {
+                Keg::Formula(_, installed) => {
+                    info_formula(&mut buf, &installed.upstream, Some(installed)).unwrap()
+                }
+                Keg::Cask(_, installed) => {
+                    info_cask(&mut buf, &installed.upstream, Some(installed)).unwrap()
+                }
+            };
+
+            let preview = String::from_utf8(buf).unwrap();
+
+            ItemPreview::AnsiText(preview)
+        }
+    }
+}
+
+pub mod bundle {
+    use std::collections::{BTreeSet, HashMap};
+    use std::io::{BufWriter, Write};
+    use std::path::{Path, PathBuf};
+
+    use clap::{Args, Parser, Subcommand};
+    use colored::Colorize;
+
+    use brewer_core::brewfile::{self, Brewfile};
+    use brewer_core::models;
+    use brewer_core::resolve::{Kind, Resolver};
+    use brewer_engine::{Engine, State};
+
+    use crate::cli::install;
+    use crate::output::{self, Format};
+    use crate::pretty::header;
+
+    #[derive(Parser)]
+    pub struct Bundle {
+        /// Path of the Brewfile, `-` dumps to stdout
+        #[clap(long, global = true, default_value = "Brewfile")]
+        pub file: PathBuf,
+
+        #[command(subcommand)]
+        pub command: Commands,
+    }
+
+    #[derive(Subcommand)]
+    pub enum Commands {
+        /// Write the taps, the formulae installed on request and the casks to the Brewfile
+        Dump(Dump),
+
+        /// Indicate by exit code whether everything in the Brewfile is installed
+        Check,
+
+        /// Install what is missing from the Brewfile
+        Install(Install),
+    }
+
+    #[derive(Args)]
+    pub struct Dump {
+        /// Overwrite an existing Brewfile
+        #[clap(long, action)]
+        pub force: bool,
+    }
+
+    #[derive(Args)]
+    pub struct Install {
+        /// Confirm
+        #[clap(short, long, action)]
+        pub yes: bool,
+
+        /// Install deprecated formulae and casks
+        #[clap(long, action)]
+        pub allow_deprecated: bool,
+    }
+
+    impl Bundle {
+        /// Returns false if something is missing or could not be installed
+        pub fn run(&self, mut engine: Engine, format: Format) -> anyhow::Result<bool> {
+            match &self.command {
+                Commands::Dump(cmd) => {
+                    let state = engine.cache_or_latest()?;
+
+                    cmd.run(&self.file, &state)?;
+
+                    Ok(true)
+                }
+                Commands::Check => {
+                    let brewfile = read(&self.file)?;
+
+                    let Some(state) = engine.cache()? else {
+                        anyhow::bail!("the cache is empty, run `brewer update` first");
+                    };
+
+                    let state = with_taps(&brewfile, state, &engine)?;
+
+                    check(&brewfile, &state, &engine.taps(), format)
+                }
+                Commands::Install(cmd) => cmd.run(&read(&self.file)?, engine),
+            }
+        }
+    }
+
+    impl Dump {
+        fn run(&self, path: &Path, state: &State) -> anyhow::Result<()> {
+            let brewfile = Brewfile::dump(&state.formulae.installed, &state.casks.installed);
+
+            if path == Path::new("-") {
+                print!("{brewfile}");
+
+                return Ok(());
+            }
+
+            if path.exists() && !self.force {
+                anyhow::bail!(
+                    "{} already exists, pass --force to overwrite it",
+                    path.display()
+                );
+            }
+
+            std::fs::write(path, brewfile.to_string())?;
+
+            println!(
+                "{}",
+                header::primary!(
+                    "Wrote {} entries to {}",
+                    brewfile.entries.len(),
+                    path.display()
+                )
+            );
+
+            Ok(())
+        }
+    }
+
+    impl Install {
+        fn run(&self, brewfile: &Brewfile, mut engine: Engine) -> anyhow::Result<bool> {
+            let mut state = engine.cache_or_latest()?;
+
+            let missing_taps: Vec<_> = brewfile
+                .missing(&state, &engine.taps())
+                .into_iter()
+                .filter(|m| m.kind == brewfile::Kind::Tap)
+                .collect();
+
+            if !missing_taps.is_empty() {
+                for tap in brewfile.taps() {
+                    if missing_taps.iter().any(|m| m.name == tap.name) {
+                        println!("{}", header::primary!("Tapping {}", tap.name));
+
+                        engine.tap(&tap.name, tap.url.as_deref())?;
+                    }
+                }
+
+                // formulae and casks of the new taps
+                engine.update(|_, _, _| {})?;
+                state = engine.cache_or_latest()?;
+            }
+
+            let state = with_taps(brewfile, state, &engine)?;
+
+            let (kegs, options, ok) = self.get_kegs(brewfile, &state);
+
+            if kegs.is_empty() {
+                println!(
+                    "{}",
+                    header::primary!("Everything in the Brewfile is installed")
+                );
+
+                return Ok(ok);
+            }
+
+            if !self.yes && !install::plan(&kegs, &state)? {
+                return Ok(ok);
+            }
+
+            let (plain, with_args): (Vec<_>, Vec<_>) = kegs.into_iter().partition(|k| match k {
+                models::Keg::Formula(f) => options
+                    .get(&f.base.full_name())
+                    .is_none_or(|o| o.args.is_empty()),
+                models::Keg::Cask(_) => true,
+            });
+
+            engine.install(plain)?;
+
+            for keg in with_args {
+                if let models::Keg::Formula(formula) = keg {
+                    let args = options[&formula.base.full_name()].install_args();
+
+                    engine.install_with(&formula, &args)?;
+                }
+            }
+
+            for (name, o) in &options {
+                if let (Some(link), Some(formula)) = (o.link, state.formulae.all.get(name)) {
+                    engine.link(formula, link)?;
+                }
+            }
+
+            Ok(ok)
+        }
+
+        /// Kegs to install with the Brewfile options of their formulae by canonical name,
+        /// false if some entries are unknown or not allowed
+        fn get_kegs<'a>(
+            &self,
+            brewfile: &'a Brewfile,
+            state: &State,
+        ) -> (
+            Vec<models::Keg>,
+            HashMap<String, &'a brewfile::Formula>,
+            bool,
+        ) {
+            let resolver = Resolver::new(&state.formulae.all, &state.casks.all);
+
+            let mut kegs = Vec::new();
+            let mut options = HashMap::new();
+            let mut ok = true;
+
+            let entries = brewfile
+                .formulae()
+                .map(|f| (Kind::Formula, &f.name, Some(f)))
+                .chain(brewfile.casks().map(|c| (Kind::Cask, &c.name, None)));
+
+            for (kind, given, formula) in entries {
+                let Some(resolved) = resolver.resolve(given, Some(kind)) else {
+                    println!("{}", header::warning!("Unknown {kind} {given}, skipping"));
+                    ok = false;
+                    continue;
+                };
+
+                let name = resolved.name;
+
+                let keg = match kind {
+                    Kind::Formula if state.formulae.installed.contains_key(&name) => continue,
+                    Kind::Cask if state.casks.installed.contains_key(&name) => continue,
+                    Kind::Formula => state
+                        .formulae
+                        .all
+                        .get(&name)
+                        .cloned()
+                        .map(models::Keg::Formula),
+                    Kind::Cask => state.casks.all.get(&name).cloned().map(models::Keg::Cask),
+                };
+
+                // the resolver only returns names from the state
+                let Some(keg) = keg else {
+                    continue;
+                };
+
+                if !install::is_allowed(&keg, self.allow_deprecated) {
+                    ok = false;
+                    continue;
+                }
+
+                if let Some(formula) = formula {
+                    options.insert(name, formula);
+                }
+
+                kegs.push(keg);
+            }
+
+            (kegs, options, ok)
+        }
+    }
+
+    /// State with the formulae and casks of the tapped Brewfile taps the cache has nothing from.
+    /// The JSON API only serves the default taps, the others are evaluated by brew
+    fn with_taps(brewfile: &Brewfile, state: State, engine: &Engine) -> anyhow::Result<State> {
+        let tapped = engine.taps();
+
+        let unserved: Vec<_> = brewfile
+            .taps()
+            .map(|tap| tap.name.to_lowercase())
+            .filter(|tap| tapped.contains(tap))
+            .filter(|tap| {
+                !state.formulae.all.values().any(|f| &f.base.tap == tap)
+                    && !state.casks.all.values().any(|c| &c.base.tap == tap)
+            })
+            .collect();
+
+        if unserved.is_empty() {
+            return Ok(state);
+        }
+
+        let taps: Vec<_> = unserved.iter().map(|t| t.as_str()).collect();
+
+        engine.with_taps(state, &taps)
+    }
+
+    fn read(path: &Path) -> anyhow::Result<Brewfile> {
+        let brewfile = Brewfile::read(path)?;
+
+        for (line, entry) in &brewfile.skipped {
+            eprintln!(
+                "{}",
+                header::warning!("Skipping unsupported entry on line {line}: {entry}")
+            );
+        }
+
+        Ok(brewfile)
+    }
+
+    /// Returns false if anything is missing
+    fn check(
+        brewfile: &Brewfile,
+        state: &State,
+        tapped: &BTreeSet<String>,
+        format: Format,
+    ) -> anyhow::Result<bool> {
+        let missing = brewfile.missing(state, tapped);
+
+        let mut buf = BufWriter::new(std::io::stdout());
+
+        match format {
+            Format::Json | Format::Ndjson => output::write(&mut buf, format, &missing)?,
+            Format::Plain => {
+                for m in &missing {
+                    writeln!(buf, "{}", m.name)?;
+                }
+            }
+            Format::Table if missing.is_empty() => {
+                writeln!(
+                    buf,
+                    "{}",
+                    header::primary!("Everything in the Brewfile is installed")
+                )?;
+            }
+            Format::Table => {
+                writeln!(
+                    buf,
+                    "{}",
+                    header::warning!("The following Brewfile entries are missing")
+                )?;
+
+                for m in &missing {
+                    let unknown = if m.known {
+                        String::new()
+                    } else {
+                        format!(" {}", "unknown".yellow())
+                    };
+
+                    writeln!(buf, "{} ({}){unknown}", m.name.cyan(), m.kind)?;
+                }
+            }
+        }
+
+        buf.flush()?;
+
+        Ok(missing.is_empty())
+    }
+}
+
+pub mod lock {
+    use std::io::{BufWriter, Write};
+    use std::path::{Path, PathBuf};
+
+    use clap::Args;
+    use colored::Colorize;
+
+    use brewer_core::lock::{Change, Lockfile};
+    use brewer_engine::Engine;
+
+    use crate::output::{self, Format};
+    use crate::pretty::header;
+
+    #[derive(Args)]
+    pub struct Lock {
+        /// Path of the lockfile, `-` writes it to stdout
+        #[clap(long, default_value = "brewer.lock.json")]
+        pub file: PathBuf,
+
+        /// Report how the prefix drifted from the lockfile instead of writing it
+        #[clap(long, action)]
+        pub check: bool,
+    }
+
+    impl Lock {
+        /// Returns false if the prefix drifted from the lockfile
+        pub fn run(&self, mut engine: Engine, format: Format) -> anyhow::Result<bool> {
+            let locked = if self.check {
+                Some(Lockfile::read(&self.file)?)
+            } else {
+                None
+            };
+
+            let state = engine.cache_or_latest()?;
+            let current = Lockfile::capture(&state.formulae.installed, &state.casks.installed);
+
+            match locked {
+                Some(locked) => check(&self.file, &locked, &current, format),
+                None => {
+                    write(&self.file, &current)?;
+
+                    Ok(true)
+                }
+            }
+        }
+    }
+
+    fn write(path: &Path, lockfile: &Lockfile) -> anyhow::Result<()> {
+        if path == Path::new("-") {
+            print!("{lockfile}");
+
+            return Ok(());
+        }
+
+        std::fs::write(path, lockfile.to_string())?;
+
+        println!(
+            "{}",
+            header::primary!(
+                "Locked {} formulae and {} casks in {}",
+                lockfile.formulae.len(),
+                lockfile.casks.len(),
+                path.display()
+            )
+        );
+
+        Ok(())
+    }
+
+    /// Returns false if anything drifted
+    fn check(
+        path: &Path,
+        locked: &Lockfile,
+        current: &Lockfile,
+        format: Format,
+    ) -> anyhow::Result<bool> {
+        let drift = locked.drift(current);
+
+        let mut buf = BufWriter::new(std::io::stdout());
+
+        match format {
+            Format::Json | Format::Ndjson => output::write(&mut buf, format, &drift)?,
+            Format::Plain => {
+                for d in &drift {
+                    writeln!(buf, "{}", d.name)?;
+                }
+            }
+            Format::Table if drift.is_empty() => {
+                writeln!(
+                    buf,
+                    "{}",
+                    header::primary!("The prefix matches {}", path.display())
+                )?;
+            }
+            Format::Table => {
+                writeln!(
+                    buf,
+                    "{}",
+                    header::warning!("The prefix drifted from {}", path.display())
+                )?;
+
+                for d in &drift {
+                    let locked = d.locked.as_deref().unwrap_or_default();
+                    let installed = d.installed.as_deref().unwrap_or_default();
+
+                    let what = match d.change {
+                        Change::Missing => format!("not installed, locked at {locked}"),
+                        Change::Unlocked => format!("{installed} is not locked"),
+                        Change::Version => format!("{} -> {}", locked, installed.yellow()),
+                        Change::Spec => format!("spec {} -> {}", locked, installed.yellow()),
+                        Change::Reason => format!("reason {} -> {}", locked, installed.yellow()),
+                    };
+
+                    writeln!(buf, "{} ({}) {what}", d.name.cyan(), d.kind)?;
+                }
+            }
         }
+
+        buf.flush()?;
+
+        Ok(drift.is_empty())
     }
 }
 
-pub mod uninstall {
-    use std::borrow::Cow;
-    use std::io::{BufWriter, Write};
-
+pub mod pin {
     use clap::Args;
-    use colored::Colorize;
-    use inquire::{Confirm, InquireError};
-    use skim::{ItemPreview, PreviewContext, SkimItem};
 
-    use brewer_core::models;
+    use brewer_core::models::pin;
+    use brewer_core::resolve::{self, Resolver};
     use brewer_engine::{Engine, State};
 
-    use crate::cli::{info_cask, info_formula, select_skim};
+    use crate::cli::{kind, notice_resolved};
     use crate::pretty::header;
 
     #[derive(Args)]
-    pub struct Uninstall {
+    pub struct Pin {
+        #[clap(required = true)]
         pub names: Vec<String>,
 
+        /// Treat the given names as formulae
         #[clap(short, long, action, group = "type")]
         pub formula: bool,
 
+        /// Treat the given names as casks
         #[clap(short, long, action, group = "type")]
         pub cask: bool,
+    }
 
-        /// Confirm
-        #[clap(short, long, action)]
-        pub yes: bool,
+    #[derive(Args)]
+    pub struct Unpin {
+        #[clap(required = true)]
+        pub names: Vec<String>,
+
+        /// Treat the given names as formulae
+        #[clap(short, long, action, group = "type")]
+        pub formula: bool,
+
+        /// Treat the given names as casks
+        #[clap(short, long, action, group = "type")]
+        pub cask: bool,
     }
 
-    impl Uninstall {
-        pub fn run(&self, mut engine: Engine) -> anyhow::Result<()> {
+    impl Pin {
+        /// Returns false if any of the names is unknown or not installed,
+        /// pinning a pinned keg again is not a failure
+        pub fn run(&self, mut engine: Engine) -> anyhow::Result<bool> {
             let state = engine.cache_or_latest()?;
+            let kegs = installed(&state, &self.names, kind(self.formula, self.cask));
 
-            let kegs = self.get_kegs(state)?;
+            let ok = kegs.len() == self.names.len();
 
-            if kegs.is_empty() {
-                Ok(())
-            } else {
-                let kegs = kegs
-                    .into_iter()
-                    .map(|k| match k {
-                        Keg::Formula(formula) => formula.upstream.into(),
-                        Keg::Cask(cask) => cask.upstream.into(),
-                    })
-                    .collect();
-
-                if self.yes || plan(&kegs)? {
-                    engine.uninstall(kegs)?;
+            for (kind, name) in kegs {
+                if engine.pin(kind, &name)? {
+                    println!("{}", header::primary!("Pinned {kind} {name}"));
+                } else {
+                    println!("{}", header::warning!("{name} is already pinned, skipping"));
                 }
-
-                Ok(())
             }
-        }
 
-        fn get_kegs(&self, state: State) -> anyhow::Result<Vec<Keg>> {
-            if self.names.is_empty() {
-                self.get_kegs_from_skim(state)
-            } else {
-                self.get_kegs_from_args(state)
-            }
+            Ok(ok)
         }
+    }
 
-        fn get_kegs_from_args(&self, mut state: State) -> anyhow::Result<Vec<Keg>> {
-            let mut kegs = Vec::new();
+    impl Unpin {
+        /// Returns false if any of the names could not be unpinned
+        pub fn run(&self, mut engine: Engine) -> anyhow::Result<bool> {
+            let state = engine.cache_or_latest()?;
+            let kegs = installed(&state, &self.names, kind(self.formula, self.cask));
 
-            for name in &self.names {
-                let keg = if self.formula {
-                    if !state.formulae.installed.contains_key(name) {
-                        println!(
-                            "{}",
-                            header::warning!("Formula {name} is not installed, skipping")
-                        );
-                        continue;
-                    }
+            let mut ok = kegs.len() == self.names.len();
 
-                    state.formulae.installed.remove(name).map(Keg::Formula)
-                } else if self.cask {
-                    if !state.casks.installed.contains_key(name) {
+            for (kind, name) in kegs {
+                match engine.unpin(kind, &name)? {
+                    Some(pin::Source::Brewer) => {
+                        println!("{}", header::primary!("Unpinned {kind} {name}"));
+                    }
+                    Some(pin::Source::Homebrew) => {
+                        println!("{}", header::primary!("Unpinned {kind} {name}"));
                         println!(
                             "{}",
-                            header::warning!("Cask {name} is not installed, skipping")
+                            header::warning!(
+                                "{name} was pinned with `brew pin`, it is unpinned in Homebrew too"
+                            )
                         );
-                        continue;
                     }
+                    None => {
+                        println!("{}", header::warning!("{name} is not pinned, skipping"));
+                        ok = false;
+                    }
+                }
+            }
 
-                    state.casks.installed.remove(name).map(Keg::Cask)
-                } else {
-                    state
-                        .formulae
-                        .installed
-                        .remove(name)
-                        .map(Keg::Formula)
-                        .or_else(|| state.casks.installed.remove(name).map(Keg::Cask))
-                };
+            Ok(ok)
+        }
+    }
 
-                let Some(keg) = keg else {
-                    println!(
-                        "{}",
-                        header::warning!("Formula or cask {name} is not installed skipping")
-                    );
-                    continue;
-                };
+    /// Kind and canonical name of the given installed kegs, warns about the others
+    fn installed(
+        state: &State,
+        names: &[String],
+        kind: Option<resolve::Kind>,
+    ) -> Vec<(resolve::Kind, String)> {
+        let resolver = Resolver::new(&state.formulae.all, &state.casks.all);
+
+        let mut kegs = Vec::new();
+
+        for given in names {
+            let Some(resolved) = notice_resolved(given, resolver.resolve(given, kind)) else {
+                println!(
+                    "{}",
+                    header::warning!("Unknown formula or cask {given}, skipping")
+                );
+                continue;
+            };
 
-                kegs.push(keg);
+            let installed = match resolved.kind {
+                resolve::Kind::Formula => state.formulae.installed.contains_key(&resolved.name),
+                resolve::Kind::Cask => state.casks.installed.contains_key(&resolved.name),
+            };
+
+            if !installed {
+                println!(
+                    "{}",
+                    header::warning!("{} is not installed, skipping", resolved.name)
+                );
+                continue;
             }
 
-            Ok(kegs)
+            kegs.push((resolved.kind, resolved.name));
         }
 
-        fn get_kegs_from_skim(&self, state: State) -> anyhow::Result<Vec<Keg>> {
-            let mut installed: Vec<Keg> =
-                Vec::with_capacity(state.formulae.installed.len() + state.casks.installed.len());
+        kegs
+    }
+}
 
-            for formula in state
-                .formulae
-                .installed
-                .into_values()
-                .filter(|f| f.receipt.installed_on_request)
-            {
-                installed.push(formula.into());
-            }
+pub mod doctor {
+    use std::io::{BufWriter, Write};
+
+    use clap::Args;
+
+    use brewer_core::doctor::Severity;
+    use brewer_engine::Engine;
 
-            for cask in state.casks.installed.into_values() {
-                installed.push(cask.into());
+    use crate::output::{self, Format};
+    use crate::pretty::header;
+
+    #[derive(Args)]
+    pub struct Doctor {}
+
+    impl Doctor {
+        /// Returns false if any warning or error was found
+        pub fn run(&self, engine: Engine, format: Format) -> anyhow::Result<bool> {
+            let findings = engine.doctor()?;
+
+            let healthy = findings.iter().all(|f| f.severity == Severity::Info);
+
+            let mut buf = BufWriter::new(std::io::stdout());
+
+            match format {
+                Format::Json | Format::Ndjson => output::write(&mut buf, format, &findings)?,
+                Format::Plain => {
+                    for finding in &findings {
+                        writeln!(buf, "{finding}")?;
+                    }
+                }
+                Format::Table => {
+                    for finding in &findings {
+                        let line = match finding.severity {
+                            Severity::Info => header::primary!("{finding}"),
+                            Severity::Warning => header::warning!("{finding}"),
+                            Severity::Error => header::error!("{finding}"),
+                        };
+
+                        writeln!(buf, "{line}")?;
+                    }
+
+                    if healthy {
+                        writeln!(buf, "{}", header::primary!("No problems found"))?;
+                    }
+                }
             }
 
-            let selected = select_skim(installed, "Uninstall", true)?
-                .into_iter()
-                .collect();
+            buf.flush()?;
 
-            Ok(selected)
+            Ok(healthy)
         }
     }
+}
 
-    fn plan(kegs: &Vec<models::Keg>) -> anyhow::Result<bool> {
-        let mut w = BufWriter::new(std::io::stderr());
+pub mod caveats {
+    use std::io::{BufWriter, Write};
 
-        writeln!(
-            w,
-            "{}",
-            header::primary!("The following kegs will be uninstalled")
-        )?;
+    use clap::Args;
 
-        for keg in kegs {
-            match &keg {
-                models::Keg::Formula(f) => writeln!(
-                    w,
-                    "{} {} (Formula)",
-                    f.base.name.cyan(),
-                    f.base.versions.stable
-                )?,
-                models::Keg::Cask(c) => {
-                    writeln!(w, "{} {} (Cask)", c.base.token.cyan(), c.base.version)?
+    use brewer_core::resolve::Resolver;
+    use brewer_engine::State;
+
+    use crate::cli::notice_resolved;
+    use crate::output::{self, Format};
+    use crate::pretty::header;
+
+    #[derive(Args)]
+    pub struct Caveats {
+        /// Installed formulae or casks, all of them if none are given
+        pub names: Vec<String>,
+    }
+
+    impl Caveats {
+        /// Returns false if any of the given names is not installed
+        pub fn run(&self, state: State, format: Format) -> anyhow::Result<bool> {
+            let mut found = true;
+            let mut records = Vec::new();
+
+            if self.names.is_empty() {
+                for f in state.formulae.installed.values() {
+                    records.push(output::Caveats::formula(&f.upstream));
+                }
+
+                for c in state.casks.installed.values() {
+                    records.push(output::Caveats::cask(&c.upstream));
+                }
+
+                records.retain(|r| r.caveats.is_some());
+                records.sort_by(|a, b| a.name.cmp(b.name));
+            } else {
+                let resolver = Resolver::new(&state.formulae.all, &state.casks.all);
+
+                for given in &self.names {
+                    let name = match notice_resolved(given, resolver.resolve(given, None)) {
+                        Some(resolved) => resolved.name,
+                        None => given.clone(),
+                    };
+
+                    if let Some(f) = state.formulae.installed.get(&name) {
+                        records.push(output::Caveats::formula(&f.upstream));
+                    } else if let Some(c) = state.casks.installed.get(&name) {
+                        records.push(output::Caveats::cask(&c.upstream));
+                    } else {
+                        eprintln!("{}", header::warning!("{name} is not installed"));
+                        found = false;
+                    }
                 }
             }
-        }
 
-        writeln!(w)?;
+            let mut buf = BufWriter::new(std::io::stdout());
 
-        let mut executables: Vec<String> = Vec::new();
+            if format.is_json() {
+                output::write(&mut buf, format, &records)?;
+            } else {
+                for record in &records {
+                    match record.caveats {
+                        Some(caveats) => {
+                            writeln!(buf, "{}", header::primary!("{}", record.name))?;
+                            writeln!(buf, "{}", caveats.trim_end())?;
+                        }
+                        None => writeln!(
+                            buf,
+                            "{}",
+                            header::primary!("{} has no caveats", record.name)
+                        )?,
+                    }
 
-        for k in kegs {
-            if let models::Keg::Formula(f) = &k {
-                for e in &f.executables {
-                    executables.push(e.purple().to_string());
+                    writeln!(buf)?;
                 }
             }
-        }
 
-        if !executables.is_empty() {
-            writeln!(
-                w,
-                "{}",
-                header::primary!("The following executables will be removed")
-            )?;
-            writeln!(w, "{}", executables.join(" "))?;
-            writeln!(w)?;
+            buf.flush()?;
+
+            Ok(found)
         }
+    }
+}
 
-        w.flush()?;
+pub mod tap {
+    use std::io::{BufWriter, Write};
 
-        let result = Confirm::new("Proceed?").with_default(false).prompt();
+    use clap::Args;
+    use colored::Colorize;
+    use prettytable::format::consts::FORMAT_CLEAN;
+    use prettytable::{row, Table};
 
-        match result {
-            Ok(value) => Ok(value),
-            Err(e) => match e {
-                InquireError::OperationCanceled => Ok(false),
-                e => Err(e.into()),
-            },
+    use brewer_core::taps;
+    use brewer_engine::State;
+
+    use crate::output::{self, Format};
+    use crate::pretty::header;
+
+    #[derive(Args)]
+    pub struct Tap {}
+
+    impl Tap {
+        pub fn run(&self, state: State, format: Format) -> anyhow::Result<()> {
+            let taps = taps::summary(&state);
+
+            let mut buf = BufWriter::new(std::io::stdout());
+
+            match format {
+                Format::Json | Format::Ndjson => output::write(&mut buf, format, &taps)?,
+                Format::Plain => {
+                    for tap in taps {
+                        writeln!(buf, "{}", tap.name)?;
+                    }
+                }
+                Format::Table => {
+                    writeln!(buf, "{}", header::primary!("Taps"))?;
+
+                    let mut table = Table::new();
+                    let mut table_format = *FORMAT_CLEAN;
+
+                    table_format.padding(0, 2);
+                    table.set_format(table_format);
+
+                    table.add_row(row!["Tap", "Formulae", "Casks", "Installed"]);
+
+                    for tap in taps {
+                        let installed = tap.installed_formulae + tap.installed_casks;
+
+                        table.add_row(row![
+                            tap.name.cyan(),
+                            r->tap.formulae,
+                            r->tap.casks,
+                            r->if installed > 0 {
+                                installed.to_string().green()
+                            } else {
+                                installed.to_string().normal()
+                            }
+                        ]);
+                    }
+
+                    table.print(&mut buf)?;
+                }
+            }
+
+            buf.flush()?;
+
+            Ok(())
         }
     }
+}
 
-    #[derive(Clone)]
-    pub enum Keg {
-        Formula(models::formula::installed::Formula),
-        Cask(models::cask::installed::Cask),
+pub mod cache {
+    use std::io::{BufWriter, Write};
+
+    use clap::{Parser, Subcommand};
+    use prettytable::format::consts::FORMAT_CLEAN;
+    use prettytable::{row, Table};
+
+    use brewer_engine::store::Info;
+    use brewer_engine::Engine;
+
+    use crate::output::{self, Format};
+    use crate::pretty::header;
+
+    #[derive(Parser)]
+    pub struct Cache {
+        #[command(subcommand)]
+        pub command: Commands,
     }
 
-    impl From<models::formula::installed::Formula> for Keg {
-        fn from(value: models::formula::installed::Formula) -> Self {
-            Keg::Formula(value)
-        }
+    #[derive(Subcommand)]
+    pub enum Commands {
+        /// Show schema version, size, last update and entry counts
+        Info,
     }
 
-    impl From<models::cask::installed::Cask> for Keg {
-        fn from(value: models::cask::installed::Cask) -> Self {
-            Keg::Cask(value)
+    impl Cache {
+        pub fn run(&self, engine: Engine, format: Format) -> anyhow::Result<()> {
+            match self.command {
+                Commands::Info => info(engine.cache_info()?, format),
+            }
         }
     }
 
-    impl SkimItem for Keg {
-        fn text(&self) -> Cow<str> {
-            match &self {
-                Keg::Formula(formula) => Cow::Borrowed(&formula.upstream.base.name),
-                Keg::Cask(cask) => Cow::Borrowed(&cask.upstream.base.token),
+    fn info(info: Info, format: Format) -> anyhow::Result<()> {
+        let mut buf = BufWriter::new(std::io::stdout());
+
+        if format.is_json() {
+            output::write_one(&mut buf, format, &info)?;
+        } else {
+            let or_unknown = |v: Option<String>| v.unwrap_or("unknown".to_string());
+
+            let mut table = Table::new();
+            let mut table_format = *FORMAT_CLEAN;
+
+            table_format.padding(0, 2);
+            table.set_format(table_format);
+
+            table.add_row(row!["Path", info.path.display()]);
+            table.add_row(row!["Size", size(info.size)]);
+            table.add_row(row![
+                "Schema",
+                or_unknown(info.schema.map(|s| s.to_string()))
+            ]);
+            table.add_row(row!["Written by", or_unknown(info.version)]);
+            table.add_row(row![
+                "Last update",
+                info.last_update
+                    .map(|u| format!("{} UTC", u.format("%Y-%m-%d %H:%M:%S")))
+                    .unwrap_or("never".to_string())
+            ]);
+            table.add_row(row!["Formulae", info.formulae]);
+            table.add_row(row!["Casks", info.casks]);
+            table.add_row(row!["Aliases", info.aliases]);
+            table.add_row(row!["Executables", info.executables]);
+
+            if format == Format::Table {
+                writeln!(buf, "{}", header::primary!("Cache"))?;
             }
+
+            table.print(&mut buf)?;
         }
 
-        fn preview(&self, _context: PreviewContext) -> ItemPreview {
-            let mut buf = Vec::new();
+        buf.flush()?;
 
-            match &self {
-                Keg::Formula(formula) => {
-                    info_formula(&mut buf, &formula.upstream, Some(formula)).unwrap()
-                }
-                Keg::Cask(cask) => info_cask(&mut buf, &cask.upstream, Some(cask)).unwrap(),
-            };
+        Ok(())
+    }
 
-            let preview = String::from_utf8(buf).unwrap();
+    fn size(bytes: u64) -> String {
+        const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
 
-            ItemPreview::AnsiText(preview)
+        let mut size = bytes as f64;
+        let mut unit = 0;
+
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+
+        if unit == 0 {
+            format!("{bytes} B")
+        } else {
+            format!("{size:.1} {}", UNITS[unit])
         }
     }
 }
@@ -1304,5 +3503,3 @@ where
         None => Ok(Vec::new()),
     }
 }
-
-fn kegs_list() {}
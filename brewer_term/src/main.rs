@@ -2,14 +2,17 @@ use std::process::exit;
 
 use clap::Parser;
 
-use brewer_core::Brew;
+use brewer_core::enrich::Enrichments;
+use brewer_core::{Brew, Source};
 use brewer_engine::Engine;
 use log::LevelFilter;
 
 use crate::cli::{Cli, Commands};
+use crate::output::Format;
 use crate::settings::AutoUpdate;
 
 mod cli;
+mod output;
 mod pretty;
 mod settings;
 
@@ -22,17 +25,23 @@ fn run() -> anyhow::Result<bool> {
 
     setup_logger(c.verbose.log_level_filter());
 
+    let format = Format::or_detect(c.format);
+
     match c.command {
         Commands::Which(cmd) => {
             let settings = settings::Settings::new()?;
 
-            let mut engine = get_engine(settings)?;
-            let state = engine.cache_or_latest()?;
+            let engine = get_engine(settings)?;
 
-            Ok(cmd.run(state)?)
+            Ok(cmd.run(engine, format)?)
         }
         Commands::Update(cmd) => {
-            let settings = settings::Settings::new()?;
+            let mut settings = settings::Settings::new()?;
+
+            if let Some(dir) = &cmd.from {
+                settings.homebrew.source = Source::Api;
+                settings.sources = settings::Sources::mirror(dir)?;
+            }
 
             let engine = get_engine(settings)?;
 
@@ -44,19 +53,23 @@ fn run() -> anyhow::Result<bool> {
             let settings = settings::Settings::new()?;
 
             let mut engine = get_engine(settings)?;
+
+            if cmd.pinned {
+                engine.ensure_pins()?;
+            }
+
             let state = engine.cache_or_latest()?;
 
-            cmd.run(state)?;
+            cmd.run(state, format)?;
 
             Ok(true)
         }
         Commands::Info(cmd) => {
             let settings = settings::Settings::new()?;
 
-            let mut engine = get_engine(settings)?;
-            let state = engine.cache_or_latest()?;
+            let engine = get_engine(settings)?;
 
-            Ok(cmd.run(state)?)
+            Ok(cmd.run(engine, format)?)
         }
         Commands::Search(cmd) => {
             let settings = settings::Settings::new()?;
@@ -64,7 +77,7 @@ fn run() -> anyhow::Result<bool> {
             let mut engine = get_engine(settings)?;
             let state = engine.cache_or_latest()?;
 
-            Ok(cmd.run(state)?)
+            Ok(cmd.run(state, format)?)
         }
         Commands::Paths(cmd) => {
             cmd.run();
@@ -74,10 +87,36 @@ fn run() -> anyhow::Result<bool> {
         Commands::Exists(cmd) => {
             let settings = settings::Settings::new()?;
 
+            let engine = get_engine(settings)?;
+
+            Ok(cmd.run(engine, format)?)
+        }
+        Commands::Deps(cmd) => {
+            let settings = settings::Settings::new()?;
+
+            let mut engine = get_engine(settings)?;
+            let state = engine.cache_or_latest()?;
+
+            Ok(cmd.run(state, format)?)
+        }
+        Commands::Uses(cmd) => {
+            let settings = settings::Settings::new()?;
+
             let mut engine = get_engine(settings)?;
             let state = engine.cache_or_latest()?;
 
-            Ok(cmd.run(state))
+            Ok(cmd.run(state, format)?)
+        }
+        Commands::Outdated(cmd) => {
+            let settings = settings::Settings::new()?;
+
+            let mut engine = get_engine(settings)?;
+
+            engine.ensure_pins()?;
+
+            let state = engine.cache_or_latest()?;
+
+            Ok(cmd.run(state, format)?)
         }
         Commands::Install(cmd) => {
             let settings = settings::Settings::new()?;
@@ -88,24 +127,102 @@ fn run() -> anyhow::Result<bool> {
 
             Ok(true)
         }
-        Commands::Uninstall(cmd) => {
+        Commands::Upgrade(cmd) => {
             let settings = settings::Settings::new()?;
 
             let engine = get_engine(settings)?;
 
             cmd.run(engine)?;
 
+            Ok(true)
+        }
+        Commands::Uninstall(cmd) => {
+            let settings = settings::Settings::new()?;
+
+            let engine = get_engine(settings)?;
+
+            Ok(cmd.run(engine)?)
+        }
+        Commands::Bundle(cmd) => {
+            let settings = settings::Settings::new()?;
+
+            let engine = get_engine(settings)?;
+
+            Ok(cmd.run(engine, format)?)
+        }
+        Commands::Lock(cmd) => {
+            let settings = settings::Settings::new()?;
+
+            let engine = get_engine(settings)?;
+
+            Ok(cmd.run(engine, format)?)
+        }
+        Commands::Pin(cmd) => {
+            let settings = settings::Settings::new()?;
+
+            let engine = get_engine(settings)?;
+
+            Ok(cmd.run(engine)?)
+        }
+        Commands::Unpin(cmd) => {
+            let settings = settings::Settings::new()?;
+
+            let engine = get_engine(settings)?;
+
+            Ok(cmd.run(engine)?)
+        }
+        Commands::Doctor(cmd) => {
+            let settings = settings::Settings::new()?;
+
+            let engine = get_engine(settings)?;
+
+            Ok(cmd.run(engine, format)?)
+        }
+        Commands::Caveats(cmd) => {
+            let settings = settings::Settings::new()?;
+
+            let mut engine = get_engine(settings)?;
+            let state = engine.cache_or_latest()?;
+
+            Ok(cmd.run(state, format)?)
+        }
+        Commands::Tap(cmd) => {
+            let settings = settings::Settings::new()?;
+
+            let mut engine = get_engine(settings)?;
+            let state = engine.cache_or_latest()?;
+
+            cmd.run(state, format)?;
+
+            Ok(true)
+        }
+        Commands::Cache(cmd) => {
+            let settings = settings::Settings::new()?;
+
+            let engine = get_engine(settings)?;
+
+            cmd.run(engine, format)?;
+
             Ok(true)
         }
     }
 }
 
-fn get_brew(settings: settings::Homebrew) -> anyhow::Result<Brew> {
+fn get_brew(
+    settings: settings::Homebrew,
+    sources: settings::Sources,
+    enrichments: Enrichments,
+) -> anyhow::Result<Brew> {
     let brew = Brew::default();
 
     let brew = brewer_core::BrewBuilder::default()
         .path(settings.path.unwrap_or(brew.path))
         .prefix(settings.prefix.unwrap_or(brew.prefix))
+        .source(settings.source)
+        .api_key(settings.api_key)
+        .verify_api(settings.verify_api.unwrap_or(brew.verify_api))
+        .urls(sources.urls())
+        .enrichments(enrichments)
         .build()?;
 
     Ok(brew)
@@ -130,7 +247,7 @@ fn get_engine(settings: settings::Settings) -> anyhow::Result<Engine> {
         engine_builder.cache_duration(None);
     }
 
-    let brew = get_brew(settings.homebrew)?;
+    let brew = get_brew(settings.homebrew, settings.sources, settings.enrichments)?;
 
     engine_builder.brew(brew);
 
@@ -149,8 +266,47 @@ fn main() {
             }
         }
         Err(e) => {
-            eprintln!("{}", pretty::header::error!("{e}"));
-            exit(1)
+            eprintln!("{}", pretty::header::error!("{e:#}"));
+
+            let (code, hint) = failure(&e);
+
+            if let Some(hint) = hint {
+                eprintln!("{hint}");
+            }
+
+            exit(code)
         }
     }
 }
+
+/// Exit code, following sysexits.h, and a hint on how to fix the failure
+fn failure(e: &anyhow::Error) -> (i32, Option<&'static str>) {
+    use brewer_core::Error;
+
+    let Some(e) = e.chain().find_map(|e| e.downcast_ref::<Error>()) else {
+        return (1, None);
+    };
+
+    match e {
+        Error::BrewMissing { .. } => (
+            69,
+            Some("Install Homebrew or set homebrew.path in the configuration"),
+        ),
+        Error::Brew { .. } => (70, None),
+        Error::PrefixMissing { .. } => (
+            66,
+            Some("Set homebrew.prefix in the configuration or the HOMEBREW_PREFIX variable"),
+        ),
+        Error::Receipt { .. } => (
+            72,
+            Some("Reinstall the formula with `brew reinstall` to rewrite its receipt"),
+        ),
+        Error::Fetch { .. } => (75, Some("Check the network connection and try again")),
+        Error::Schema { .. } => (76, Some("The upstream format might have changed")),
+        Error::Signature(_) => (77, None),
+        Error::Brewfile { .. } => (64, None),
+        Error::Lockfile { .. } => (65, Some("Write it again with `brewer lock`")),
+        Error::Config(_) => (78, None),
+        Error::Io { .. } => (74, None),
+    }
+}
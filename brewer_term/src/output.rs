@@ -0,0 +1,305 @@
+//! Machine-readable output of the read commands.
+//!
+//! The schema of every record is documented in the README and should only
+//! be extended in a backwards compatible way.
+
+use std::io::{IsTerminal, Write};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use brewer_core::{models, outdated};
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Pretty-printed JSON document
+    Json,
+
+    /// One compact JSON record per line
+    Ndjson,
+
+    /// Names only, one per line
+    Plain,
+
+    /// Human-readable tables
+    Table,
+}
+
+impl Format {
+    /// Use the given format or pick one based on whether stdout is a terminal
+    pub fn or_detect(format: Option<Format>) -> Format {
+        match format {
+            Some(format) => format,
+            None if std::io::stdout().is_terminal() => Format::Table,
+            None => Format::Plain,
+        }
+    }
+
+    pub fn is_json(self) -> bool {
+        matches!(self, Format::Json | Format::Ndjson)
+    }
+}
+
+/// Write records as a JSON array or as newline delimited JSON.
+pub fn write<T: Serialize>(
+    w: &mut impl Write,
+    format: Format,
+    records: &[T],
+) -> anyhow::Result<()> {
+    match format {
+        Format::Ndjson => {
+            for record in records {
+                serde_json::to_writer(&mut *w, record)?;
+                writeln!(w)?;
+            }
+        }
+        _ => {
+            serde_json::to_writer_pretty(&mut *w, records)?;
+            writeln!(w)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write a single record as a JSON document or as one line of JSON.
+pub fn write_one<T: Serialize>(
+    w: &mut impl Write,
+    format: Format,
+    record: &T,
+) -> anyhow::Result<()> {
+    match format {
+        Format::Ndjson => serde_json::to_writer(&mut *w, record)?,
+        _ => serde_json::to_writer_pretty(&mut *w, record)?,
+    }
+
+    writeln!(w)?;
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Keg<'a> {
+    Formula(Formula<'a>),
+    Cask(Cask<'a>),
+}
+
+impl<'a> Keg<'a> {
+    pub fn formula(
+        formula: &'a models::formula::Formula,
+        installed: Option<&'a models::formula::installed::Formula>,
+    ) -> Self {
+        let mut executables: Vec<_> = formula.executables.iter().map(String::as_str).collect();
+        let mut aliases: Vec<_> = formula.base.aliases.iter().map(String::as_str).collect();
+
+        executables.sort_unstable();
+        aliases.sort_unstable();
+
+        Keg::Formula(Formula {
+            name: &formula.base.name,
+            tap: &formula.base.tap,
+            version: &formula.base.versions.stable,
+            head: formula.base.versions.head.as_deref(),
+            desc: formula.base.desc.as_deref(),
+            homepage: formula.base.homepage.as_deref(),
+            aliases,
+            dependencies: &formula.base.dependencies,
+            build_dependencies: &formula.base.build_dependencies,
+            deprecated: formula.base.deprecated,
+            deprecation_reason: formula.base.deprecation_reason.as_deref(),
+            disabled: formula.base.disabled,
+            disable_reason: formula.base.disable_reason.as_deref(),
+            executables,
+            analytics: formula.analytics.as_ref().map(Analytics::from),
+            installed: installed.map(|i| InstalledFormula {
+                version: &i.version,
+                head: matches!(i.receipt.source.spec, models::formula::receipt::Spec::Head),
+                installed_on_request: i.receipt.installed_on_request,
+                installed_as_dependency: i.receipt.installed_as_dependency,
+                pinned: i.pinned,
+            }),
+        })
+    }
+
+    pub fn cask(
+        cask: &'a models::cask::Cask,
+        installed: Option<&'a models::cask::installed::Cask>,
+    ) -> Self {
+        let mut names: Vec<_> = cask.base.names.iter().map(String::as_str).collect();
+
+        names.sort_unstable();
+
+        Keg::Cask(Cask {
+            token: &cask.base.token,
+            tap: &cask.base.tap,
+            version: &cask.base.version,
+            desc: cask.base.desc.as_deref(),
+            homepage: cask.base.homepage.as_deref(),
+            names,
+            deprecated: cask.base.deprecated,
+            deprecation_reason: cask.base.deprecation_reason.as_deref(),
+            disabled: cask.base.disabled,
+            disable_reason: cask.base.disable_reason.as_deref(),
+            analytics: cask.analytics.as_ref().map(Analytics::from),
+            installed: installed.map(|i| {
+                let mut versions: Vec<_> = i.versions.iter().map(String::as_str).collect();
+
+                versions.sort_unstable();
+
+                InstalledCask {
+                    versions,
+                    pinned: i.pinned,
+                }
+            }),
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct Formula<'a> {
+    pub name: &'a str,
+    pub tap: &'a str,
+    pub version: &'a str,
+    pub head: Option<&'a str>,
+    pub desc: Option<&'a str>,
+    pub homepage: Option<&'a str>,
+    pub aliases: Vec<&'a str>,
+    pub dependencies: &'a [String],
+    pub build_dependencies: &'a [String],
+    pub deprecated: bool,
+    pub deprecation_reason: Option<&'a str>,
+    pub disabled: bool,
+    pub disable_reason: Option<&'a str>,
+    pub executables: Vec<&'a str>,
+    pub analytics: Option<Analytics>,
+    pub installed: Option<InstalledFormula<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct InstalledFormula<'a> {
+    pub version: &'a str,
+    pub head: bool,
+    pub installed_on_request: bool,
+    pub installed_as_dependency: bool,
+    pub pinned: bool,
+}
+
+#[derive(Serialize)]
+pub struct Analytics {
+    /// Position in the 30 days install ranking, 1 being the most popular
+    pub rank: Option<i64>,
+
+    pub install: Installs,
+    pub install_on_request: Installs,
+}
+
+/// Install counts per window, None if not in the ranking
+#[derive(Serialize)]
+pub struct Installs {
+    #[serde(rename = "30d")]
+    pub days30: Option<u64>,
+
+    #[serde(rename = "90d")]
+    pub days90: Option<u64>,
+
+    #[serde(rename = "365d")]
+    pub days365: Option<u64>,
+}
+
+impl From<&models::analytics::Analytics> for Analytics {
+    fn from(analytics: &models::analytics::Analytics) -> Self {
+        Analytics {
+            rank: analytics.install.days30.map(|c| c.rank),
+            install: Installs::from(&analytics.install),
+            install_on_request: Installs::from(&analytics.install_on_request),
+        }
+    }
+}
+
+impl From<&models::analytics::Windows> for Installs {
+    fn from(windows: &models::analytics::Windows) -> Self {
+        Installs {
+            days30: windows.days30.map(|c| c.count),
+            days90: windows.days90.map(|c| c.count),
+            days365: windows.days365.map(|c| c.count),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Cask<'a> {
+    pub token: &'a str,
+    pub tap: &'a str,
+    pub version: &'a str,
+    pub desc: Option<&'a str>,
+    pub homepage: Option<&'a str>,
+    pub names: Vec<&'a str>,
+    pub deprecated: bool,
+    pub deprecation_reason: Option<&'a str>,
+    pub disabled: bool,
+    pub disable_reason: Option<&'a str>,
+    pub analytics: Option<Analytics>,
+    pub installed: Option<InstalledCask<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct InstalledCask<'a> {
+    pub versions: Vec<&'a str>,
+    pub pinned: bool,
+}
+
+#[derive(Serialize)]
+pub struct Exists<'a> {
+    pub name: &'a str,
+    pub exists: bool,
+
+    /// `formula`, `cask` or null if nothing was found
+    #[serde(rename = "type")]
+    pub kind: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Outdated<'a> {
+    Formula(&'a outdated::Formula),
+    Cask(&'a outdated::Cask),
+}
+
+#[derive(Serialize)]
+pub struct Caveats<'a> {
+    pub name: &'a str,
+
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+
+    pub caveats: Option<&'a str>,
+}
+
+impl<'a> Caveats<'a> {
+    pub fn formula(formula: &'a models::formula::Formula) -> Self {
+        Caveats {
+            name: &formula.base.name,
+            kind: "formula",
+            caveats: formula.base.caveats.as_deref(),
+        }
+    }
+
+    pub fn cask(cask: &'a models::cask::Cask) -> Self {
+        Caveats {
+            name: &cask.base.token,
+            kind: "cask",
+            caveats: cask.base.caveats.as_deref(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Dependency {
+    pub name: String,
+    pub installed: bool,
+
+    /// Present only for the tree output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dependencies: Option<Vec<Dependency>>,
+}
@@ -2,6 +2,8 @@ use colored::Colorize;
 use prettytable::{cell, Row, Table};
 use prettytable::format::consts::FORMAT_CLEAN;
 
+use brewer_core::models::keg::Status;
+
 pub mod header {
     macro_rules! primary {
         ($($arg:tt)*) => {{
@@ -38,6 +40,20 @@ pub mod header {
     pub(crate) use error;
 }
 
+/// Marker of deprecated and disabled kegs, None if the keg is active
+pub fn status(status: Status) -> Option<String> {
+    match status {
+        Status::Active => None,
+        Status::Deprecated(_) => Some("deprecated".yellow().to_string()),
+        Status::Disabled(_) => Some("disabled".red().to_string()),
+    }
+}
+
+/// Marker of pinned kegs
+pub fn pinned() -> String {
+    "pinned".blue().to_string()
+}
+
 pub fn bool(b: bool) -> String {
     if b {
         "✔".green().to_string()
@@ -46,6 +62,22 @@ pub fn bool(b: bool) -> String {
     }
 }
 
+/// Number with thousands separators, e.g. 12,345
+pub fn count(n: u64) -> String {
+    let digits = n.to_string();
+    let mut s = String::with_capacity(digits.len() + digits.len() / 3);
+
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            s.push(',');
+        }
+
+        s.push(c);
+    }
+
+    s
+}
+
 pub fn table(values: &[String], max_width: u16) -> Table {
     const RIGHT_PADDING: usize = 2;
 
@@ -1,9 +1,12 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
 
+use brewer_core::enrich::Enrichments;
+use brewer_core::{Source, Urls};
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum AutoUpdate {
@@ -29,6 +32,50 @@ pub struct Cache {
 pub struct Homebrew {
     pub path: Option<PathBuf>,
     pub prefix: Option<PathBuf>,
+
+    /// Where formulae and casks are loaded from
+    #[serde(default)]
+    pub source: Source,
+
+    /// Public key used to verify the JSON API
+    pub api_key: Option<PathBuf>,
+
+    /// Verify the JSON API signature, enabled by default
+    pub verify_api: Option<bool>,
+}
+
+/// Overrides of the data locations, http(s) or `file://` URLs
+#[derive(Deserialize, Default)]
+pub struct Sources {
+    pub formulae: Option<String>,
+    pub casks: Option<String>,
+    pub executables: Option<String>,
+    pub analytics: Option<String>,
+}
+
+impl Sources {
+    /// Every source read from a local mirror directory
+    pub fn mirror(dir: &Path) -> brewer_core::Result<Sources> {
+        let urls = Urls::mirror(dir)?;
+
+        Ok(Sources {
+            formulae: Some(urls.formulae),
+            casks: Some(urls.casks),
+            executables: Some(urls.executables),
+            analytics: Some(urls.analytics),
+        })
+    }
+
+    pub fn urls(self) -> Urls {
+        let urls = Urls::default();
+
+        Urls {
+            formulae: self.formulae.unwrap_or(urls.formulae),
+            casks: self.casks.unwrap_or(urls.casks),
+            executables: self.executables.unwrap_or(urls.executables),
+            analytics: self.analytics.unwrap_or(urls.analytics),
+        }
+    }
 }
 
 #[derive(Deserialize, Default)]
@@ -38,6 +85,13 @@ pub struct Settings {
 
     #[serde(default)]
     pub cache: Cache,
+
+    #[serde(default)]
+    pub sources: Sources,
+
+    /// Optional data merged into the formulae
+    #[serde(default)]
+    pub enrichments: Enrichments,
 }
 
 impl Settings {
@@ -64,4 +118,3 @@ impl Settings {
         settings.try_deserialize()
     }
 }
-